@@ -2,12 +2,20 @@ use serde::{Deserialize, Serialize};
 
 use crate::{HomeAssistantConfig, _default_off_state, _default_on_state};
 
-use super::{ComputerAssistantConfig, Name, Publishable};
+use super::source::SourceValue;
+use super::{ComputerAssistantConfig, EntityMetadata, Name, Publishable, ScriptOptions};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BinarySensor {
     pub name: Name,
-    pub state_script: String,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    /// Required unless the state is read from a source
+    pub state_script: Option<String>,
+    #[serde(flatten)]
+    pub source: SourceValue,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
 }
 
 impl BinarySensor {
@@ -19,12 +27,21 @@ impl BinarySensor {
         );
         HomeAssistantConfig::BinarySensor {
             base_topic: "".to_string(),
-            state_topic: base_stat_switch_topic,
+            state_topic: self
+                .source
+                .state_topic(config)
+                .unwrap_or(base_stat_switch_topic),
             availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
             device: config.device.clone(),
             name: self.name.to_string(),
             unique_id: self.name.as_id(),
-            value_template: Some("{{value}}".to_string()),
+            metadata: self.metadata.clone(),
+            json_attributes_topic: self.json_attributes_topic(config),
+            value_template: Some(
+                self.source
+                    .binary_value_template()
+                    .unwrap_or_else(|| "{{value}}".to_string()),
+            ),
             state_on: _default_on_state(),
             state_off: _default_off_state(),
         }
@@ -33,7 +50,7 @@ impl BinarySensor {
 
 impl Publishable for BinarySensor {
     fn state_script(&self) -> &str {
-        &self.state_script
+        self.state_script.as_deref().unwrap_or_default()
     }
 
     fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
@@ -43,4 +60,8 @@ impl Publishable for BinarySensor {
             self.name.as_id()
         )
     }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
 }
@@ -1,53 +1,121 @@
-use std::process::Command;
-
+use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 
 use crate::{HomeAssistantConfig, _default_payload_press};
 
-use super::{ComputerAssistantConfig, Name, Updateable};
+use super::{script, ComputerAssistantConfig, EntityMetadata, Name, Updateable};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Button {
     pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
     pub command_script: String,
+    /// Timeout for the command script, in seconds
+    pub timeout: Option<u64>,
 }
 
 impl Button {
-    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
-        let base_cmd_switch_topic = format!(
-            "{}/entities/cmd/button/{}",
+    fn command_topic(&self, config: &ComputerAssistantConfig, component: &str) -> String {
+        format!(
+            "{}/entities/cmd/{}/{}",
             config.base_topic,
+            component,
             self.name.as_id()
-        );
+        )
+    }
 
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
         HomeAssistantConfig::Button {
             base_topic: Default::default(),
-            command_topic: base_cmd_switch_topic,
+            command_topic: self.command_topic(config, "button"),
             availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
             device: config.device.clone(),
             name: self.name.to_string(),
             unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
             value_template: Some("{{value}}".to_string()),
             payload_press: _default_payload_press(),
         }
     }
-}
 
-impl Updateable for Button {
-    fn update(&self, topic: &[&str], state: &str) -> anyhow::Result<()> {
+    /// Run the command script when `payload` is sent to this entity as a `component`
+    async fn press(
+        &self,
+        config: &ComputerAssistantConfig,
+        topic: &[&str],
+        state: &str,
+        component: &str,
+        payload: &str,
+    ) -> anyhow::Result<()> {
         match topic {
-            ["button", id] if id == &self.name.as_id() => {
-                if state != "PRESS" {
+            [topic_component, id] if topic_component == &component && id == &self.name.as_id() => {
+                if state != payload {
                     return Ok(());
                 }
-                Command::new("bash")
-                    .arg("-c")
-                    .arg(&self.command_script)
-                    .spawn()?
-                    .wait()?;
+                script::run(
+                    &self.command_script,
+                    &[],
+                    config.script_timeout(self.timeout),
+                )
+                .await?;
             }
             _ => {}
         };
         Ok(())
     }
 }
+
+impl Updateable for Button {
+    fn update<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(self.press(config, topic, state, "button", "PRESS"))
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        vec![self.command_topic(config, "button")]
+    }
+}
+
+/// Same as a button, but activated with `ON` and shown as a scene in homeassistant
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(transparent)]
+pub struct Scene(Button);
+
+impl Scene {
+    pub fn name(&self) -> &Name {
+        &self.0.name
+    }
+
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
+        HomeAssistantConfig::Scene {
+            base_topic: Default::default(),
+            command_topic: self.0.command_topic(config, "scene"),
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.0.name.to_string(),
+            unique_id: self.0.name.as_id(),
+            metadata: self.0.metadata.clone(),
+            payload_on: "ON".to_string(),
+        }
+    }
+}
+
+impl Updateable for Scene {
+    fn update<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(self.0.press(config, topic, state, "scene", "ON"))
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        vec![self.0.command_topic(config, "scene")]
+    }
+}
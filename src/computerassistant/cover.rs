@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::HomeAssistantConfig;
+
+use super::{
+    script, ComputerAssistantConfig, EntityMetadata, Name, Publishable, ScriptOptions, Updateable,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Cover {
+    pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    /// Prints one of `open`, `opening`, `closed`, `closing` or `stopped`
+    pub state_script: String,
+    pub open_script: String,
+    pub close_script: String,
+    pub stop_script: Option<String>,
+    /// Prints the current position, between 0 (closed) and 100 (open)
+    pub position_script: Option<String>,
+    /// Receives the position as `$1`
+    pub set_position_script: Option<String>,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+}
+
+/// State payload of a cover or a valve. Covers split it with value templates, valves read it
+/// as is
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CoverState {
+    pub state: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<u64>,
+}
+
+impl Cover {
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
+        let base_cmd_cover_topic = format!(
+            "{}/entities/cmd/cover/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        let base_stat_cover_topic = format!(
+            "{}/entities/stat/cover/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        HomeAssistantConfig::Cover {
+            base_topic: "".to_string(),
+            command_topic: base_cmd_cover_topic.clone(),
+            state_topic: base_stat_cover_topic.clone(),
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.name.to_string(),
+            unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+            json_attributes_topic: self.json_attributes_topic(config),
+            value_template: Some("{{ value_json.state }}".to_string()),
+            // homeassistant only hides the stop button when this is explicitly null
+            payload_stop: self.stop_script.as_ref().map(|_| "STOP".to_string()),
+            position_topic: self.position_script.as_ref().map(|_| base_stat_cover_topic),
+            position_template: self
+                .position_script
+                .as_ref()
+                .map(|_| "{{ value_json.position }}".to_string()),
+            set_position_topic: self
+                .set_position_script
+                .as_ref()
+                .map(|_| format!("{}/position", base_cmd_cover_topic)),
+        }
+    }
+
+    async fn state(&self, config: &ComputerAssistantConfig) -> anyhow::Result<CoverState> {
+        let timeout = config.script_timeout(self.scripts.timeout);
+        let position = async {
+            let Some(position_script) = &self.position_script else {
+                return Ok(None);
+            };
+            let output = script::stdout(position_script, &[], timeout).await?;
+            Ok::<_, anyhow::Error>(Some(output.parse::<f64>()?.round() as u64))
+        };
+        let (state, position) =
+            tokio::try_join!(script::stdout(&self.state_script, &[], timeout), position)?;
+
+        Ok(CoverState { state, position })
+    }
+}
+
+impl Publishable for Cover {
+    fn state_script(&self) -> &str {
+        &self.state_script
+    }
+
+    fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!(
+            "{}/entities/stat/cover/{}",
+            config.base_topic,
+            self.name.as_id()
+        )
+    }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
+
+    fn state_payload<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<String>> {
+        Box::pin(async move { Ok(serde_json::to_string(&self.state(config).await?)?) })
+    }
+}
+
+impl Updateable for Cover {
+    fn update<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let timeout = config.script_timeout(self.scripts.timeout);
+            match topic {
+                ["cover", id] if id == &self.name.as_id() => {
+                    let script = match state {
+                        "OPEN" => &self.open_script,
+                        "CLOSE" => &self.close_script,
+                        "STOP" => self
+                            .stop_script
+                            .as_ref()
+                            .ok_or_else(|| anyhow::anyhow!("No stop_script configured"))?,
+                        _ => return Err(anyhow::anyhow!("Unknown cover command: {}", state)),
+                    };
+                    script::run(script, &[], timeout).await?;
+                }
+                ["cover", id, "position"] if id == &self.name.as_id() => {
+                    if let Some(script) = &self.set_position_script {
+                        script::run(script, &[state], timeout).await?;
+                    }
+                }
+                _ => {}
+            };
+            Ok(())
+        })
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        let base_cmd_cover_topic = format!(
+            "{}/entities/cmd/cover/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        vec![
+            format!("{}/position", base_cmd_cover_topic),
+            base_cmd_cover_topic,
+        ]
+    }
+
+    fn refresh_topic(&self, config: &ComputerAssistantConfig) -> Option<String> {
+        Some(Publishable::state_topic(self, config))
+    }
+
+    fn settle_delay(&self) -> Duration {
+        Duration::from_millis(self.scripts.settle_delay_ms)
+    }
+}
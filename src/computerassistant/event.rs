@@ -0,0 +1,189 @@
+use mqtt::{AsyncClient, MessageBuilder};
+use paho_mqtt as mqtt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::sync::mpsc;
+
+use crate::HomeAssistantConfig;
+
+use super::{script, ComputerAssistantConfig, EntityMetadata, Name};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Event {
+    pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    /// Every event type the script may print
+    pub event_types: Vec<String>,
+    /// Long-running script printing one event per line, as the event type optionally followed
+    /// by a JSON object with extra attributes
+    pub event_script: String,
+    /// Also register a device trigger for each event type, to be used in automations
+    #[serde(default)]
+    pub device_triggers: bool,
+}
+
+impl Event {
+    pub fn event_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!(
+            "{}/entities/stat/event/{}",
+            config.base_topic,
+            self.name.as_id()
+        )
+    }
+
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
+        HomeAssistantConfig::Event {
+            base_topic: "".to_string(),
+            state_topic: self.event_topic(config),
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.name.to_string(),
+            unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+            event_types: self.event_types.clone(),
+        }
+    }
+
+    /// Device triggers for every event type, along with their ids
+    pub fn device_trigger_ha_configs(
+        &self,
+        config: &ComputerAssistantConfig,
+    ) -> Vec<(String, HomeAssistantConfig)> {
+        if !self.device_triggers {
+            return vec![];
+        }
+
+        self.event_types
+            .iter()
+            .map(|event_type| {
+                let id = format!("{}_{}", self.name.as_id(), trigger_id(event_type));
+                let trigger = HomeAssistantConfig::DeviceTrigger {
+                    automation_type: "trigger".to_string(),
+                    topic: self.event_topic(config),
+                    trigger_type: event_type.clone(),
+                    subtype: self.name.to_string(),
+                    payload: event_type.clone(),
+                    value_template: "{{ value_json.event_type }}".to_string(),
+                    device: config.device.clone(),
+                };
+                (id, trigger)
+            })
+            .collect()
+    }
+
+    /// Run the event script forever, publishing every event it prints
+    pub async fn publish_events_mqtt(
+        &self,
+        config: &ComputerAssistantConfig,
+        client: &AsyncClient,
+    ) -> anyhow::Result<()> {
+        let (lines_tx, mut lines_rx) = mpsc::channel(16);
+        let watcher = script::watch(&self.event_script, lines_tx);
+
+        let publisher = async {
+            while let Some(line) = lines_rx.recv().await {
+                let event = match parse_event(&line) {
+                    Ok(event) => event,
+                    Err(err) => {
+                        println!("Ignoring event for {}: {:#}", self.name.as_str(), err);
+                        continue;
+                    }
+                };
+                if !self
+                    .event_types
+                    .iter()
+                    .any(|t| Some(t.as_str()) == event["event_type"].as_str())
+                {
+                    println!(
+                        "Ignoring unknown event for {}: {}",
+                        self.name.as_str(),
+                        line
+                    );
+                    continue;
+                }
+
+                let msg = MessageBuilder::new()
+                    .topic(self.event_topic(config))
+                    .payload(event.to_string())
+                    .qos(mqtt::QOS_1)
+                    .finalize();
+                client.publish(msg).await?;
+            }
+            Ok::<_, anyhow::Error>(())
+        };
+
+        tokio::try_join!(watcher, publisher)?;
+        Ok(())
+    }
+}
+
+/// Parse an `event_type {"some": "attributes"}` line into the payload homeassistant expects
+fn parse_event(line: &str) -> anyhow::Result<Value> {
+    let line = line.trim();
+    let (event_type, attributes) = match line.split_once(char::is_whitespace) {
+        Some((event_type, attributes)) => (event_type, attributes.trim()),
+        None => (line, ""),
+    };
+    if event_type.is_empty() {
+        return Err(anyhow::anyhow!("empty event"));
+    }
+
+    let mut event = if attributes.is_empty() {
+        Map::new()
+    } else {
+        match serde_json::from_str(attributes)? {
+            Value::Object(attributes) => attributes,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "attributes must be a JSON object: {}",
+                    line
+                ))
+            }
+        }
+    };
+    event.insert("event_type".to_string(), Value::from(event_type));
+    Ok(Value::Object(event))
+}
+
+/// Homeassistant ignores discovery topics with anything but `[a-zA-Z0-9_-]` in their id
+fn trigger_id(event_type: &str) -> String {
+    event_type
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_event() {
+        assert_eq!(
+            parse_event("pressed").unwrap(),
+            serde_json::json!({"event_type": "pressed"})
+        );
+        assert_eq!(
+            parse_event("pressed {\"button\": 2}").unwrap(),
+            serde_json::json!({"event_type": "pressed", "button": 2})
+        );
+        assert!(parse_event("pressed [1, 2]").is_err());
+        assert!(parse_event("").is_err());
+    }
+
+    #[test]
+    fn test_trigger_id() {
+        assert_eq!(trigger_id("pressed"), "pressed");
+        assert_eq!(trigger_id("double press"), "double_press");
+        assert_eq!(trigger_id("key.F13"), "key_f13");
+        assert_eq!(trigger_id("long-press"), "long-press");
+    }
+}
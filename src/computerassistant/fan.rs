@@ -0,0 +1,202 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::homeassistant::State;
+use crate::HomeAssistantConfig;
+
+use super::{
+    script, ComputerAssistantConfig, EntityMetadata, Name, Publishable, ScriptOptions, Updateable,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Fan {
+    pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    /// Exit code tells whether the fan is on
+    pub state_script: String,
+    pub on_script: String,
+    pub off_script: String,
+    /// Prints the current speed, between 0 and 100
+    pub percentage_state_script: Option<String>,
+    /// Receives the speed as `$1`, between 0 and 100
+    pub percentage_command_script: Option<String>,
+    #[serde(default)]
+    pub preset_modes: Vec<String>,
+    /// Prints the current preset mode
+    pub preset_mode_state_script: Option<String>,
+    /// Receives the preset mode as `$1`
+    pub preset_mode_command_script: Option<String>,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+}
+
+/// State payload of a fan, split by homeassistant with value templates
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FanState {
+    pub state: State,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percentage: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset_mode: Option<String>,
+}
+
+impl Fan {
+    fn supports_percentage(&self) -> bool {
+        self.percentage_command_script.is_some()
+    }
+
+    fn supports_preset_mode(&self) -> bool {
+        self.preset_mode_command_script.is_some() && !self.preset_modes.is_empty()
+    }
+
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
+        let base_cmd_fan_topic = format!(
+            "{}/entities/cmd/fan/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        let base_stat_fan_topic = format!(
+            "{}/entities/stat/fan/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+
+        let percentage = self.supports_percentage();
+        let preset_mode = self.supports_preset_mode();
+        HomeAssistantConfig::Fan {
+            base_topic: "".to_string(),
+            command_topic: base_cmd_fan_topic.clone(),
+            state_topic: base_stat_fan_topic.clone(),
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.name.to_string(),
+            unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+            json_attributes_topic: self.json_attributes_topic(config),
+            state_value_template: "{{ value_json.state }}".to_string(),
+            percentage_command_topic: percentage
+                .then(|| format!("{}/percentage", base_cmd_fan_topic)),
+            percentage_state_topic: percentage.then(|| base_stat_fan_topic.clone()),
+            percentage_value_template: percentage
+                .then(|| "{{ value_json.percentage }}".to_string()),
+            preset_mode_command_topic: preset_mode
+                .then(|| format!("{}/preset_mode", base_cmd_fan_topic)),
+            preset_mode_state_topic: preset_mode.then(|| base_stat_fan_topic.clone()),
+            preset_mode_value_template: preset_mode
+                .then(|| "{{ value_json.preset_mode }}".to_string()),
+            preset_modes: preset_mode.then(|| self.preset_modes.clone()),
+        }
+    }
+
+    async fn state(&self, config: &ComputerAssistantConfig) -> anyhow::Result<FanState> {
+        let timeout = config.script_timeout(self.scripts.timeout);
+        let percentage = async {
+            let Some(script) = &self.percentage_state_script else {
+                return Ok(None);
+            };
+            let output = script::stdout(script, &[], timeout).await?;
+            Ok::<_, anyhow::Error>(Some(output.parse::<f64>()?.round() as u64))
+        };
+        let preset_mode = async {
+            let Some(script) = &self.preset_mode_state_script else {
+                return Ok(None);
+            };
+            Ok::<_, anyhow::Error>(Some(script::stdout(script, &[], timeout).await?))
+        };
+        let (state, percentage, preset_mode) = tokio::try_join!(
+            script::state(&self.state_script, &[], timeout),
+            percentage,
+            preset_mode
+        )?;
+
+        Ok(FanState {
+            state,
+            percentage,
+            preset_mode,
+        })
+    }
+}
+
+impl Publishable for Fan {
+    fn state_script(&self) -> &str {
+        &self.state_script
+    }
+
+    fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!(
+            "{}/entities/stat/fan/{}",
+            config.base_topic,
+            self.name.as_id()
+        )
+    }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
+
+    fn state_payload<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<String>> {
+        Box::pin(async move { Ok(serde_json::to_string(&self.state(config).await?)?) })
+    }
+}
+
+impl Updateable for Fan {
+    fn update<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let timeout = config.script_timeout(self.scripts.timeout);
+            match topic {
+                ["fan", id] if id == &self.name.as_id() => {
+                    let script = match State::from_str(state)? {
+                        State::On => &self.on_script,
+                        State::Off => &self.off_script,
+                    };
+                    script::run(script, &[], timeout).await?;
+                }
+                ["fan", id, "percentage"] if id == &self.name.as_id() => {
+                    if let Some(script) = &self.percentage_command_script {
+                        script::run(script, &[state], timeout).await?;
+                    }
+                }
+                ["fan", id, "preset_mode"] if id == &self.name.as_id() => {
+                    if let Some(script) = &self.preset_mode_command_script {
+                        script::run(script, &[state], timeout).await?;
+                    }
+                }
+                _ => {}
+            };
+            Ok(())
+        })
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        let base_cmd_fan_topic = format!(
+            "{}/entities/cmd/fan/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        vec![
+            format!("{}/percentage", base_cmd_fan_topic),
+            format!("{}/preset_mode", base_cmd_fan_topic),
+            base_cmd_fan_topic,
+        ]
+    }
+
+    fn refresh_topic(&self, config: &ComputerAssistantConfig) -> Option<String> {
+        Some(Publishable::state_topic(self, config))
+    }
+
+    fn settle_delay(&self) -> Duration {
+        Duration::from_millis(self.scripts.settle_delay_ms)
+    }
+}
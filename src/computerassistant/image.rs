@@ -0,0 +1,139 @@
+use futures::future::BoxFuture;
+use mqtt::Message;
+use paho_mqtt as mqtt;
+use serde::{Deserialize, Serialize};
+
+use crate::{HomeAssistantConfig, _default_image_content_type, _default_payload_press};
+
+use super::{
+    script, ComputerAssistantConfig, EntityMetadata, Name, Publishable, ScriptOptions, Updateable,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Image {
+    pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    /// Writes the image bytes to stdout
+    pub capture_script: String,
+    /// Format of the captured image, such as `image/png` or `image/jpeg`
+    #[serde(default = "_default_image_content_type")]
+    pub content_type: String,
+    /// Also register a button that captures a new image right away
+    #[serde(default)]
+    pub capture_button: bool,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+}
+
+impl Image {
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
+        HomeAssistantConfig::Image {
+            base_topic: "".to_string(),
+            image_topic: self.state_topic(config),
+            content_type: self.content_type.clone(),
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.name.to_string(),
+            unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+            json_attributes_topic: self.json_attributes_topic(config),
+        }
+    }
+
+    /// Configuration of the companion button capturing this image, if enabled
+    pub fn capture_button_ha_config(
+        &self,
+        config: &ComputerAssistantConfig,
+    ) -> Option<HomeAssistantConfig> {
+        if !self.capture_button {
+            return None;
+        }
+
+        let base_cmd_image_topic = format!(
+            "{}/entities/cmd/image/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+
+        Some(HomeAssistantConfig::Button {
+            base_topic: Default::default(),
+            command_topic: base_cmd_image_topic,
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: format!("{} capture", self.name.as_str()),
+            unique_id: self.capture_button_id(),
+            metadata: Default::default(),
+            value_template: Some("{{value}}".to_string()),
+            payload_press: _default_payload_press(),
+        })
+    }
+
+    pub fn capture_button_id(&self) -> String {
+        format!("{}_capture", self.name.as_id())
+    }
+}
+
+impl Publishable for Image {
+    fn state_script(&self) -> &str {
+        &self.capture_script
+    }
+
+    fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!(
+            "{}/entities/stat/image/{}",
+            config.base_topic,
+            self.name.as_id()
+        )
+    }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
+
+    fn publish_state<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<Message>> {
+        // images are binary, so they skip the text state payload
+        Box::pin(async move {
+            let output = script::output(
+                &self.capture_script,
+                &[],
+                config.script_timeout(self.scripts.timeout),
+            )
+            .await?;
+            if !output.status.success() {
+                return Err(anyhow::anyhow!(
+                    "capture script failed with {}",
+                    output.status
+                ));
+            }
+            Ok(self.state_message(config, output.stdout))
+        })
+    }
+}
+
+impl Updateable for Image {
+    fn update<'a>(
+        &'a self,
+        _config: &'a ComputerAssistantConfig,
+        _topic: &'a [&'a str],
+        _state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        // pressing the capture button only needs the refresh that follows every command
+        Box::pin(async { Ok(()) })
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        vec![format!(
+            "{}/entities/cmd/image/{}",
+            config.base_topic,
+            self.name.as_id()
+        )]
+    }
+
+    fn refresh_topic(&self, config: &ComputerAssistantConfig) -> Option<String> {
+        Some(Publishable::state_topic(self, config))
+    }
+}
@@ -0,0 +1,320 @@
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::homeassistant::State;
+use crate::{HomeAssistantConfig, _default_brightness_scale};
+
+use super::{
+    script, ComputerAssistantConfig, EntityMetadata, Name, Publishable, ScriptOptions, Updateable,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Light {
+    pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    /// Exit code tells whether the light is on
+    pub state_script: String,
+    pub on_script: String,
+    pub off_script: String,
+    /// Prints the current brightness, between 0 and `brightness_scale`
+    pub brightness_state_script: Option<String>,
+    /// Receives the brightness as `$1`
+    pub brightness_command_script: Option<String>,
+    #[serde(default = "_default_brightness_scale")]
+    pub brightness_scale: u64,
+    /// Prints the current color as `r,g,b`
+    pub rgb_state_script: Option<String>,
+    /// Receives the color as `$1`, `$2` and `$3`
+    pub rgb_command_script: Option<String>,
+    /// Prints the current color temperature, in mireds
+    pub color_temp_state_script: Option<String>,
+    /// Receives the color temperature in mireds as `$1`
+    pub color_temp_command_script: Option<String>,
+    pub min_mireds: Option<u64>,
+    pub max_mireds: Option<u64>,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+    /// Only apply the latest command received within this many milliseconds
+    pub debounce_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+/// Command and state payload of a light using homeassistant's JSON schema
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LightState {
+    pub state: State,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<Rgb>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_temp: Option<u64>,
+}
+
+impl Light {
+    fn supports_brightness(&self) -> bool {
+        self.brightness_command_script.is_some()
+    }
+
+    fn supports_rgb(&self) -> bool {
+        self.rgb_command_script.is_some()
+    }
+
+    fn supports_color_temp(&self) -> bool {
+        self.color_temp_command_script.is_some()
+    }
+
+    fn supported_color_modes(&self) -> Vec<String> {
+        let mut modes = vec![];
+        if self.supports_rgb() {
+            modes.push("rgb".to_string());
+        }
+        if self.supports_color_temp() {
+            modes.push("color_temp".to_string());
+        }
+        if modes.is_empty() {
+            // brightness is implied by any color mode, so it's only listed on its own
+            modes.push(if self.supports_brightness() {
+                "brightness".to_string()
+            } else {
+                "onoff".to_string()
+            });
+        }
+        modes
+    }
+
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
+        let base_cmd_light_topic = format!(
+            "{}/entities/cmd/light/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        let base_stat_light_topic = format!(
+            "{}/entities/stat/light/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        HomeAssistantConfig::Light {
+            base_topic: "".to_string(),
+            schema: "json".to_string(),
+            command_topic: base_cmd_light_topic,
+            state_topic: base_stat_light_topic,
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.name.to_string(),
+            unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+            json_attributes_topic: self.json_attributes_topic(config),
+            brightness: self.supports_brightness(),
+            brightness_scale: self.brightness_scale,
+            supported_color_modes: self.supported_color_modes(),
+            min_mireds: self.min_mireds,
+            max_mireds: self.max_mireds,
+        }
+    }
+
+    /// Run an optional state script, parsing its output
+    async fn optional_state<T>(
+        &self,
+        config: &ComputerAssistantConfig,
+        script: &Option<String>,
+        parse: impl FnOnce(&str) -> anyhow::Result<T>,
+    ) -> anyhow::Result<Option<T>> {
+        let Some(script) = script else {
+            return Ok(None);
+        };
+        let output =
+            script::stdout(script, &[], config.script_timeout(self.scripts.timeout)).await?;
+        Ok(Some(parse(&output)?))
+    }
+
+    async fn state(&self, config: &ComputerAssistantConfig) -> anyhow::Result<LightState> {
+        let (state, brightness, color, color_temp) = tokio::try_join!(
+            script::state(
+                &self.state_script,
+                &[],
+                config.script_timeout(self.scripts.timeout)
+            ),
+            self.optional_state(config, &self.brightness_state_script, parse_number),
+            self.optional_state(config, &self.rgb_state_script, parse_rgb),
+            self.optional_state(config, &self.color_temp_state_script, parse_number),
+        )?;
+
+        let color_mode = if color.is_some() {
+            Some("rgb".to_string())
+        } else if color_temp.is_some() {
+            Some("color_temp".to_string())
+        } else {
+            self.supported_color_modes().into_iter().next()
+        };
+
+        Ok(LightState {
+            state,
+            brightness,
+            color_mode,
+            color,
+            color_temp,
+        })
+    }
+
+    async fn command(
+        &self,
+        config: &ComputerAssistantConfig,
+        command: LightState,
+    ) -> anyhow::Result<()> {
+        let timeout = config.script_timeout(self.scripts.timeout);
+        if command.state == State::Off {
+            script::run(&self.off_script, &[], timeout).await?;
+            return Ok(());
+        }
+
+        // the on script may set a brightness of its own, which would flash before the requested
+        // one is applied, so it only runs when the light has to be turned on
+        let has_attributes =
+            command.brightness.is_some() || command.color.is_some() || command.color_temp.is_some();
+        if !has_attributes || script::state(&self.state_script, &[], timeout).await? == State::Off {
+            script::run(&self.on_script, &[], timeout).await?;
+        }
+        if let (Some(brightness), Some(script)) =
+            (command.brightness, &self.brightness_command_script)
+        {
+            script::run(script, &[&brightness.to_string()], timeout).await?;
+        }
+        if let (Some(color), Some(script)) = (command.color, &self.rgb_command_script) {
+            let (r, g, b) = (
+                color.r.to_string(),
+                color.g.to_string(),
+                color.b.to_string(),
+            );
+            script::run(script, &[&r, &g, &b], timeout).await?;
+        }
+        if let (Some(color_temp), Some(script)) =
+            (command.color_temp, &self.color_temp_command_script)
+        {
+            script::run(script, &[&color_temp.to_string()], timeout).await?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_number(output: &str) -> anyhow::Result<u64> {
+    Ok(output.parse::<f64>()?.round() as u64)
+}
+
+fn parse_rgb(output: &str) -> anyhow::Result<Rgb> {
+    let channels = output
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|channel| !channel.is_empty())
+        .map(str::parse::<u8>)
+        .collect::<Result<Vec<_>, _>>()?;
+    match channels.as_slice() {
+        [r, g, b] => Ok(Rgb {
+            r: *r,
+            g: *g,
+            b: *b,
+        }),
+        _ => Err(anyhow::anyhow!("Expected an r,g,b color, got: {}", output)),
+    }
+}
+
+impl Publishable for Light {
+    fn state_script(&self) -> &str {
+        &self.state_script
+    }
+
+    fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!(
+            "{}/entities/stat/light/{}",
+            config.base_topic,
+            self.name.as_id()
+        )
+    }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
+
+    fn state_payload<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<String>> {
+        Box::pin(async move { Ok(serde_json::to_string(&self.state(config).await?)?) })
+    }
+}
+
+impl Updateable for Light {
+    fn update<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            match topic {
+                ["light", id] if id == &self.name.as_id() => {
+                    self.command(config, serde_json::from_str(state)?).await?;
+                }
+                _ => {}
+            };
+            Ok(())
+        })
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        vec![format!(
+            "{}/entities/cmd/light/{}",
+            config.base_topic,
+            self.name.as_id()
+        )]
+    }
+
+    fn refresh_topic(&self, config: &ComputerAssistantConfig) -> Option<String> {
+        Some(Publishable::state_topic(self, config))
+    }
+
+    fn settle_delay(&self) -> Duration {
+        Duration::from_millis(self.scripts.settle_delay_ms)
+    }
+
+    fn debounce(&self) -> Option<Duration> {
+        self.debounce_ms.map(Duration::from_millis)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_rgb() {
+        let expected = Rgb {
+            r: 255,
+            g: 128,
+            b: 0,
+        };
+        assert_eq!(parse_rgb("255,128,0").unwrap(), expected);
+        assert_eq!(parse_rgb("255 128 0").unwrap(), expected);
+        assert!(parse_rgb("255,128").is_err());
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let command: LightState =
+            serde_json::from_str(r#"{"state":"ON","brightness":80,"color":{"r":1,"g":2,"b":3}}"#)
+                .unwrap();
+        assert_eq!(command.state, State::On);
+        assert_eq!(command.brightness, Some(80));
+        assert_eq!(command.color, Some(Rgb { r: 1, g: 2, b: 3 }));
+    }
+}
@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::HomeAssistantConfig;
+
+use super::{
+    script, ComputerAssistantConfig, EntityMetadata, Name, Publishable, ScriptOptions, Updateable,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Lock {
+    pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    /// Exit code tells whether it is locked
+    pub state_script: String,
+    pub lock_script: String,
+    pub unlock_script: String,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+}
+
+impl Lock {
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
+        let base_cmd_lock_topic = format!(
+            "{}/entities/cmd/lock/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        let base_stat_lock_topic = format!(
+            "{}/entities/stat/lock/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        HomeAssistantConfig::Lock {
+            base_topic: "".to_string(),
+            command_topic: base_cmd_lock_topic,
+            state_topic: base_stat_lock_topic,
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.name.to_string(),
+            unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+            json_attributes_topic: self.json_attributes_topic(config),
+            value_template: Some("{{value}}".to_string()),
+        }
+    }
+}
+
+impl Publishable for Lock {
+    fn state_script(&self) -> &str {
+        &self.state_script
+    }
+
+    fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!(
+            "{}/entities/stat/lock/{}",
+            config.base_topic,
+            self.name.as_id()
+        )
+    }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
+
+    fn state_payload<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<String>> {
+        Box::pin(async move {
+            let status = script::run(
+                &self.state_script,
+                &[],
+                config.script_timeout(self.scripts.timeout),
+            )
+            .await?;
+            let state = if status.success() {
+                "LOCKED"
+            } else {
+                "UNLOCKED"
+            };
+            Ok(state.to_string())
+        })
+    }
+}
+
+impl Updateable for Lock {
+    fn update<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            match topic {
+                ["lock", id] if id == &self.name.as_id() => {
+                    let script = match state {
+                        "LOCK" => &self.lock_script,
+                        "UNLOCK" => &self.unlock_script,
+                        _ => return Err(anyhow::anyhow!("Unknown lock command: {}", state)),
+                    };
+                    script::run(script, &[], config.script_timeout(self.scripts.timeout)).await?;
+                }
+                _ => {}
+            };
+            Ok(())
+        })
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        vec![format!(
+            "{}/entities/cmd/lock/{}",
+            config.base_topic,
+            self.name.as_id()
+        )]
+    }
+
+    fn refresh_topic(&self, config: &ComputerAssistantConfig) -> Option<String> {
+        Some(Publishable::state_topic(self, config))
+    }
+
+    fn settle_delay(&self) -> Duration {
+        Duration::from_millis(self.scripts.settle_delay_ms)
+    }
+}
@@ -0,0 +1,178 @@
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{HomeAssistantConfig, _default_media_metadata_keys};
+
+use super::button::Button;
+use super::number::Number;
+use super::{
+    parse_attributes, script, ComputerAssistantConfig, EntityMetadata, Name, Publishable,
+    ScriptOptions,
+};
+
+/// A group of entities controlling a media player, e.g. through `playerctl`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Media {
+    pub name: Name,
+    /// Passed along to every entity of the group
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    pub play_pause_script: Option<String>,
+    pub next_script: Option<String>,
+    pub previous_script: Option<String>,
+    /// Prints a JSON object describing what is playing, such as its title and artist
+    pub metadata_script: Option<String>,
+    /// Metadata keys that get their own sensor. Every key is also available as an attribute,
+    /// unless there is an `attributes_script`
+    #[serde(default = "_default_media_metadata_keys")]
+    pub metadata_keys: Vec<String>,
+    /// Prints the current volume, between 0 and 100
+    pub volume_state_script: Option<String>,
+    /// Receives the volume as `$1`
+    pub volume_command_script: Option<String>,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+}
+
+impl Media {
+    fn entity_name(&self, suffix: &str) -> Name {
+        Name(format!("{} {}", self.name.as_str(), suffix))
+    }
+
+    /// Buttons for every configured playback control
+    pub fn buttons(&self) -> Vec<Button> {
+        [
+            ("play pause", &self.play_pause_script),
+            ("next", &self.next_script),
+            ("previous", &self.previous_script),
+        ]
+        .into_iter()
+        .filter_map(|(suffix, script)| {
+            script.as_ref().map(|script| Button {
+                name: self.entity_name(suffix),
+                metadata: self.metadata.clone(),
+                command_script: script.clone(),
+                timeout: self.scripts.timeout,
+            })
+        })
+        .collect()
+    }
+
+    /// Volume number, when both volume scripts are configured
+    pub fn volume(&self) -> Option<Number> {
+        let (Some(state_script), Some(command_script)) =
+            (&self.volume_state_script, &self.volume_command_script)
+        else {
+            return None;
+        };
+
+        Some(Number {
+            name: self.entity_name("volume"),
+            metadata: self.metadata.clone(),
+            state_script: state_script.clone(),
+            command_script: command_script.clone(),
+            min: 0.0,
+            max: 100.0,
+            step: 1.0,
+            unit_of_measurement: "%".to_string(),
+            scripts: ScriptOptions {
+                // the watcher must not run twice, so with metadata it runs along with it and
+                // refreshes the volume from there
+                watch_script: self
+                    .scripts
+                    .watch_script
+                    .clone()
+                    .filter(|_| !self.has_metadata()),
+                attributes_script: None,
+                ..self.scripts.clone()
+            },
+            debounce_ms: None,
+        })
+    }
+
+    pub fn has_metadata(&self) -> bool {
+        self.metadata_script.is_some()
+    }
+
+    /// Sensors for every metadata key, along with their ids
+    pub fn metadata_ha_configs(
+        &self,
+        config: &ComputerAssistantConfig,
+    ) -> Vec<(String, HomeAssistantConfig)> {
+        if !self.has_metadata() {
+            return vec![];
+        }
+
+        self.metadata_keys
+            .iter()
+            .map(|key| {
+                let name = self.entity_name(key);
+                let sensor = HomeAssistantConfig::Sensor {
+                    base_topic: "".to_string(),
+                    state_topic: self.state_topic(config),
+                    availability_topic: format!(
+                        "{}/{}",
+                        config.base_topic, config.availability_topic
+                    ),
+                    device: config.device.clone(),
+                    name: name.to_string(),
+                    unique_id: name.as_id(),
+                    metadata: self.metadata.clone(),
+                    // a JSON string is also a valid template string, whatever the key contains
+                    value_template: Some(format!(
+                        "{{{{ value_json[{}] | default('') }}}}",
+                        Value::from(key.as_str())
+                    )),
+                    unit_of_measurement: None,
+                    suggested_display_precision: None,
+                    json_attributes_topic: Some(
+                        self.json_attributes_topic(config)
+                            .unwrap_or_else(|| self.state_topic(config)),
+                    ),
+                };
+                (name.as_id(), sensor)
+            })
+            .collect()
+    }
+}
+
+impl Publishable for Media {
+    fn state_script(&self) -> &str {
+        self.metadata_script.as_deref().unwrap_or_default()
+    }
+
+    fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!(
+            "{}/entities/stat/media/{}",
+            config.base_topic,
+            self.name.as_id()
+        )
+    }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
+
+    fn watch_refresh_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        self.volume()
+            .map(|volume| volume.state_topic(config))
+            .into_iter()
+            .collect()
+    }
+
+    fn state_payload<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<String>> {
+        Box::pin(async move {
+            let output = script::stdout(
+                self.state_script(),
+                &[],
+                config.script_timeout(self.scripts.timeout),
+            )
+            .await?;
+            parse_attributes(&output)
+        })
+    }
+}
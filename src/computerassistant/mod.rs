@@ -1,56 +1,185 @@
 mod binary_sensor;
 mod button;
+mod cover;
+mod event;
+mod fan;
+mod image;
+mod light;
+mod lock;
+mod media;
+mod notify;
 mod number;
+mod presence;
+mod script;
+mod select;
 mod sensor;
+mod siren;
+mod source;
 mod switch;
+mod text;
+mod update;
+mod valve;
 
+use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
-use std::process::Command;
+use std::time::{Duration, Instant};
 
+use futures::future::{join_all, try_join_all, BoxFuture};
 use mqtt::{AsyncClient, AsyncReceiver, Message, MessageBuilder};
-use paho_mqtt::{self as mqtt, QOS_1};
+use paho_mqtt as mqtt;
 
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
 
-use crate::homeassistant::State;
-use crate::HomeAssistantDevice;
+use crate::{HomeAssistantConfig, HomeAssistantDevice};
 
 use self::binary_sensor::BinarySensor;
-use self::button::Button;
+use self::button::{Button, Scene};
+use self::cover::Cover;
+use self::event::Event;
+use self::fan::Fan;
+use self::image::Image;
+use self::light::Light;
+use self::lock::Lock;
+use self::media::Media;
+use self::notify::Notify;
 use self::number::Number;
+use self::presence::Presence;
+use self::select::Select;
 use self::sensor::Sensor;
+use self::siren::Siren;
+use self::source::Source;
 use self::switch::Switch;
+use self::text::Text;
+pub use self::text::TextMode;
+use self::update::Update;
+use self::valve::Valve;
 
-pub trait Updateable {
-    fn update(&self, topic: &[&str], state: &str) -> anyhow::Result<()>;
+pub trait Updateable: Send + Sync {
+    fn update<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>>;
+    /// Topics this entity receives commands on
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String>;
+    /// State topic to refresh right after a command for this entity was handled
+    fn refresh_topic(&self, _config: &ComputerAssistantConfig) -> Option<String> {
+        None
+    }
+    /// How long to wait for a command to take effect before refreshing the state
+    fn settle_delay(&self) -> Duration {
+        Duration::ZERO
+    }
+    /// Wait this long after a command and only apply the latest one received in the meantime
+    fn debounce(&self) -> Option<Duration> {
+        None
+    }
 }
 
-pub trait Publishable {
+pub trait Publishable: Send + Sync {
     fn state_script(&self) -> &str;
     fn state_topic(&self, config: &ComputerAssistantConfig) -> String;
-    fn state_payload(&self, _config: &ComputerAssistantConfig) -> anyhow::Result<String> {
-        let script = Command::new("bash")
-            .arg("-c")
-            .arg(self.state_script())
-            .spawn()?
-            .wait()?;
-
-        Ok(if script.success() {
-            State::On
-        } else {
-            State::Off
-        }
-        .to_string())
-    }
-    fn publish_state(&self, config: &ComputerAssistantConfig) -> anyhow::Result<mqtt::Message> {
-        Ok(MessageBuilder::new()
+    fn scripts(&self) -> &ScriptOptions;
+    /// Entities may publish only their attributes, leaving their state to homeassistant
+    fn has_state(&self) -> bool {
+        true
+    }
+    fn state_payload<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<String>> {
+        Box::pin(async move {
+            let timeout = config.script_timeout(self.scripts().timeout);
+            Ok(script::state(self.state_script(), &[], timeout)
+                .await?
+                .to_string())
+        })
+    }
+    fn state_message(&self, config: &ComputerAssistantConfig, payload: Vec<u8>) -> mqtt::Message {
+        MessageBuilder::new()
             .topic(self.state_topic(config))
-            .payload(self.state_payload(config)?)
+            .payload(payload)
             .qos(mqtt::QOS_1)
-            .finalize())
+            .retained(config.retain_state)
+            .finalize()
+    }
+    fn publish_state<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<mqtt::Message>> {
+        Box::pin(async move {
+            let payload = self.state_payload(config).await?;
+            Ok(self.state_message(config, payload.into_bytes()))
+        })
+    }
+    /// State topics of other entities to refresh whenever this entity's watch script prints
+    fn watch_refresh_topics(&self, _config: &ComputerAssistantConfig) -> Vec<String> {
+        vec![]
+    }
+    fn attributes_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!("{}/attributes", self.state_topic(config))
+    }
+    /// Attributes topic to advertise in the discovery payload, when there are attributes
+    fn json_attributes_topic(&self, config: &ComputerAssistantConfig) -> Option<String> {
+        self.scripts()
+            .attributes_script
+            .as_ref()
+            .map(|_| self.attributes_topic(config))
+    }
+    fn publish_attributes<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<mqtt::Message>> {
+        Box::pin(async move {
+            let Some(attributes_script) = &self.scripts().attributes_script else {
+                return Err(anyhow::anyhow!("No attributes_script configured"));
+            };
+            let timeout = config.script_timeout(self.scripts().timeout);
+            let output = script::stdout(attributes_script, &[], timeout).await?;
+            Ok(MessageBuilder::new()
+                .topic(self.attributes_topic(config))
+                .payload(parse_attributes(&output)?)
+                .qos(mqtt::QOS_1)
+                .retained(config.retain_state)
+                .finalize())
+        })
     }
 }
 
+/// How an entity runs its scripts, shared by every entity publishing a state
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ScriptOptions {
+    /// Timeout for the entity's scripts, in seconds. Falls back to the global `script_timeout`
+    pub timeout: Option<u64>,
+    /// Interval between state updates, in seconds. Falls back to `status_pub_interval`
+    pub interval: Option<u64>,
+    /// Long-running script printing a line whenever the state may have changed
+    pub watch_script: Option<String>,
+    #[serde(default)]
+    pub watch_mode: WatchMode,
+    /// How long to wait after a command before publishing the new state, in milliseconds. Only
+    /// used by entities taking commands
+    #[serde(default)]
+    pub settle_delay_ms: u64,
+    /// Prints a JSON object published as the entity's attributes
+    pub attributes_script: Option<String>,
+}
+
+/// What to do with each line printed by a `watch_script`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchMode {
+    /// Run the `state_script` again and publish its result
+    #[default]
+    Trigger,
+    /// Publish the line itself as the new state. Entities making up a state of their own, such
+    /// as the JSON of lights or the bytes of images, can't use it
+    State,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DisplayPrecision(u8);
 
@@ -72,6 +201,28 @@ impl From<DisplayPrecision> for u8 {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityCategory {
+    Config,
+    Diagnostic,
+}
+
+/// How homeassistant presents an entity, passed along as is in its discovery payload
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct EntityMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_class: Option<String>,
+    /// Needed for homeassistant to keep long-term statistics, e.g. `measurement`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_class: Option<String>,
+    /// Such as `mdi:thermometer`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity_category: Option<EntityCategory>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Name(String);
 
@@ -95,6 +246,19 @@ impl Name {
     }
 }
 
+/// Make sure attributes are a JSON object, as homeassistant expects
+fn parse_attributes(output: &str) -> anyhow::Result<String> {
+    match serde_json::from_str(output)? {
+        serde_json::Value::Object(attributes) => {
+            Ok(serde_json::Value::Object(attributes).to_string())
+        }
+        _ => Err(anyhow::anyhow!(
+            "attributes must be a JSON object: {}",
+            output
+        )),
+    }
+}
+
 fn _default_homeassistant_topic() -> String {
     "homeassistant".to_string()
 }
@@ -103,23 +267,49 @@ fn _default_availability_topic() -> String {
     "status".to_string()
 }
 
+fn _default_force_update_interval() -> u64 {
+    300
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ComputerAssistantConfig {
     pub base_topic: String,
     pub device: HomeAssistantDevice,
     pub name: Name,
     pub unique_id: String,
+    /// Default interval between state updates, in seconds
     pub status_pub_interval: u64,
+    /// Default timeout for every script, in seconds. Scripts run without a timeout if unset
+    pub script_timeout: Option<u64>,
+    /// States are only published when they change, or after this many seconds without changes
+    #[serde(default = "_default_force_update_interval")]
+    pub force_update_interval: u64,
+    /// Publish states as retained messages, so homeassistant gets them right after a restart
+    #[serde(default)]
+    pub retain_state: bool,
     #[serde(default = "_default_homeassistant_topic")]
     pub homeassistant_topic: String,
     #[serde(default = "_default_availability_topic")]
     pub availability_topic: String,
+    /// Topic, relative to `base_topic`, where failed commands are reported
+    pub error_topic: Option<String>,
+}
+
+impl ComputerAssistantConfig {
+    /// Resolve the timeout of an entity's scripts, falling back to the global one
+    pub fn script_timeout(&self, entity_timeout: Option<u64>) -> Option<Duration> {
+        entity_timeout
+            .or(self.script_timeout)
+            .map(Duration::from_secs)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     pub computer_assistant: ComputerAssistantConfig,
     #[serde(default)]
+    pub source: Vec<Source>,
+    #[serde(default)]
     pub binary_sensor: Vec<BinarySensor>,
     #[serde(default)]
     pub sensor: Vec<Sensor>,
@@ -129,9 +319,45 @@ pub struct Config {
     pub number: Vec<Number>,
     #[serde(default)]
     pub button: Vec<Button>,
+    #[serde(default)]
+    pub select: Vec<Select>,
+    #[serde(default)]
+    pub text: Vec<Text>,
+    #[serde(default)]
+    pub light: Vec<Light>,
+    #[serde(default)]
+    pub event: Vec<Event>,
+    #[serde(default)]
+    pub notify: Vec<Notify>,
+    #[serde(default)]
+    pub media: Vec<Media>,
+    #[serde(default)]
+    pub lock: Vec<Lock>,
+    #[serde(default)]
+    pub cover: Vec<Cover>,
+    #[serde(default)]
+    pub valve: Vec<Valve>,
+    #[serde(default)]
+    pub fan: Vec<Fan>,
+    #[serde(default)]
+    pub scene: Vec<Scene>,
+    #[serde(default)]
+    pub update: Vec<Update>,
+    #[serde(default)]
+    pub presence: Vec<Presence>,
+    #[serde(default)]
+    pub siren: Vec<Siren>,
+    #[serde(default)]
+    pub image: Vec<Image>,
 }
 
-pub struct UpdateableHandlers(Vec<Box<dyn Updateable + Send + Sync>>);
+pub struct UpdateableHandlers {
+    handlers: Vec<Box<dyn Updateable>>,
+    /// Index of the handler for each command topic
+    routes: HashMap<String, usize>,
+    /// Request an immediate state update from the publishing side, by state topic
+    refresh: HashMap<String, mpsc::Sender<()>>,
+}
 
 impl UpdateableHandlers {
     pub async fn listen_mqtt(
@@ -145,44 +371,343 @@ impl UpdateableHandlers {
             .subscribe(format!("{}/#", base_command_topic), mqtt::QOS_1)
             .await?;
 
-        while let Some(message) = stream.recv().await? {
-            let topic = message.topic().to_string();
-            if !topic.starts_with(&base_command_topic) {
-                // ignore messages for other topics
-                continue;
+        // every entity handles its commands in order, but independently of the others
+        let (senders, workers): (Vec<_>, Vec<_>) = self
+            .handlers
+            .iter()
+            .map(|handler| {
+                let (commands_tx, commands_rx) = mpsc::channel(32);
+                let worker = self.handle_commands(config, client, handler.as_ref(), commands_rx);
+                (commands_tx, worker)
+            })
+            .unzip();
+
+        let dispatcher = async move {
+            while let Some(message) = stream.recv().await? {
+                self.dispatch(&senders, message);
             }
-            let subtopics = topic
-                .trim_start_matches(base_command_topic.as_str())
-                .trim_matches('/')
-                .split('/')
-                .collect::<Vec<_>>();
-            let state = std::str::from_utf8(message.payload())?;
-
-            for handler in &self.0 {
-                handler.update(&subtopics, state)?;
+            Ok(())
+        };
+
+        // once the dispatcher stops, the workers finish whatever they already received
+        let (result, _) = tokio::join!(dispatcher, join_all(workers));
+        result
+    }
+
+    /// Hand a command over to the worker of the entity handling its topic
+    fn dispatch(&self, senders: &[mpsc::Sender<Message>], message: Message) {
+        let Some(&index) = self.routes.get(message.topic()) else {
+            // no entity is interested in this topic
+            return;
+        };
+        // waiting on a busy entity would hold back the commands of every other one
+        match senders[index].try_send(message) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(message)) => println!(
+                "Dropping command on {}, its entity is still busy",
+                message.topic()
+            ),
+            Err(mpsc::error::TrySendError::Closed(message)) => println!(
+                "Dropping command on {}, its entity stopped handling commands",
+                message.topic()
+            ),
+        }
+    }
+
+    async fn handle_commands(
+        &self,
+        config: &ComputerAssistantConfig,
+        client: &AsyncClient,
+        handler: &dyn Updateable,
+        mut commands: mpsc::Receiver<Message>,
+    ) {
+        let base_command_topic = format!("{}/entities/cmd", config.base_topic);
+
+        while let Some(message) = next_command(&mut commands, handler.debounce()).await {
+            let topic = message.topic();
+            let subtopics = command_subtopics(&base_command_topic, topic);
+
+            // a bad command must not bring the whole session down
+            if let Err(err) = self
+                .handle_message(config, handler, &subtopics, &message)
+                .await
+            {
+                let entity = subtopics.join("/");
+                println!(
+                    "Failed to handle command for {} on {}: {:#}",
+                    entity, topic, err
+                );
+                Self::publish_error(config, client, &entity, topic, &message, &err).await;
             }
-            tokio::time::sleep(std::time::Duration::from_millis(500)).await
         }
+    }
+
+    async fn handle_message(
+        &self,
+        config: &ComputerAssistantConfig,
+        handler: &dyn Updateable,
+        subtopics: &[&str],
+        message: &Message,
+    ) -> anyhow::Result<()> {
+        let state = std::str::from_utf8(message.payload())?;
+
+        handler.update(config, subtopics, state).await?;
+        self.refresh_state(config, handler);
         Ok(())
     }
+
+    /// Report a failed command on the error topic, if one is configured
+    async fn publish_error(
+        config: &ComputerAssistantConfig,
+        client: &AsyncClient,
+        entity: &str,
+        topic: &str,
+        message: &Message,
+        err: &anyhow::Error,
+    ) {
+        let Some(error_topic) = &config.error_topic else {
+            return;
+        };
+
+        let payload = serde_json::json!({
+            "entity": entity,
+            "topic": topic,
+            "payload": String::from_utf8_lossy(message.payload()),
+            "error": format!("{:#}", err),
+        });
+        let msg = MessageBuilder::new()
+            .topic(format!("{}/{}", config.base_topic, error_topic))
+            .payload(payload.to_string())
+            .qos(mqtt::QOS_1)
+            .finalize();
+        if let Err(err) = client.publish(msg).await {
+            println!("Failed to publish error for {}: {}", entity, err);
+        }
+    }
+
+    /// Ask for the state of an entity to be published again once its command settled
+    fn refresh_state(&self, config: &ComputerAssistantConfig, handler: &dyn Updateable) {
+        let Some(refresh) = handler
+            .refresh_topic(config)
+            .and_then(|topic| self.refresh.get(&topic))
+        else {
+            return;
+        };
+
+        let refresh = refresh.clone();
+        let settle_delay = handler.settle_delay();
+        tokio::spawn(async move {
+            tokio::time::sleep(settle_delay).await;
+            // a refresh that is already pending will do just as well
+            let _ = refresh.try_send(());
+        });
+    }
+}
+
+/// Wait for the next command. With a debounce, only the latest of a burst of commands is kept
+async fn next_command<T>(
+    commands: &mut mpsc::Receiver<T>,
+    debounce: Option<Duration>,
+) -> Option<T> {
+    let mut command = commands.recv().await?;
+    if let Some(debounce) = debounce {
+        tokio::time::sleep(debounce).await;
+        while let Ok(newer) = commands.try_recv() {
+            command = newer;
+        }
+    }
+    Some(command)
 }
 
-pub struct PublishableHandlers(Vec<Box<dyn Publishable + Send + Sync>>);
+/// Split a command topic into what comes after the base command topic
+fn command_subtopics<'a>(base_command_topic: &str, topic: &'a str) -> Vec<&'a str> {
+    topic
+        .trim_start_matches(base_command_topic)
+        .trim_matches('/')
+        .split('/')
+        .collect()
+}
+
+pub struct PublishableHandlers {
+    handlers: Vec<Box<dyn Publishable>>,
+    /// Requests for an immediate state update, one for each handler
+    refresh: Vec<mpsc::Receiver<()>>,
+    /// Other entities to refresh whenever a watch script prints, one list for each handler
+    watch_refresh: Vec<Vec<mpsc::Sender<()>>>,
+    events: Vec<Event>,
+}
 
 impl PublishableHandlers {
+    /// Publish the state of every entity, each one on its own interval, along with every
+    /// event as it happens.
+    ///
+    /// Only returns if publishing to the broker fails.
     pub async fn publish_state_mqtt(
-        &self,
+        self,
+        config: &ComputerAssistantConfig,
+        client: &AsyncClient,
+    ) -> anyhow::Result<()> {
+        // every entity runs its own loop so a slow script doesn't hold back the others
+        let states = try_join_all(
+            self.handlers
+                .iter()
+                .zip(self.refresh)
+                .zip(self.watch_refresh)
+                .map(|((handler, refresh), watch_refresh)| {
+                    Self::publish_on_interval(
+                        handler.as_ref(),
+                        config,
+                        client,
+                        refresh,
+                        watch_refresh,
+                    )
+                }),
+        );
+        let events = try_join_all(
+            self.events
+                .iter()
+                .map(|event| event.publish_events_mqtt(config, client)),
+        );
+        tokio::try_join!(states, events)?;
+        Ok(())
+    }
+
+    async fn publish_on_interval(
+        handler: &dyn Publishable,
+        config: &ComputerAssistantConfig,
+        client: &AsyncClient,
+        refresh_rx: mpsc::Receiver<()>,
+        watch_refresh: Vec<mpsc::Sender<()>>,
+    ) -> anyhow::Result<()> {
+        let (watch_tx, watch_rx) = mpsc::channel(16);
+        let watcher = async move {
+            match &handler.scripts().watch_script {
+                Some(watch_script) => script::watch(watch_script, watch_tx).await,
+                None => Ok(()),
+            }
+        };
+
+        tokio::try_join!(
+            watcher,
+            Self::publish_on_change(handler, config, client, watch_rx, refresh_rx, watch_refresh)
+        )?;
+        Ok(())
+    }
+
+    /// Publish the state of an entity on its interval, whenever its watch script prints and
+    /// whenever a command was handled for it
+    async fn publish_on_change(
+        handler: &dyn Publishable,
+        config: &ComputerAssistantConfig,
+        client: &AsyncClient,
+        mut watch_rx: mpsc::Receiver<String>,
+        mut refresh_rx: mpsc::Receiver<()>,
+        watch_refresh: Vec<mpsc::Sender<()>>,
+    ) -> anyhow::Result<()> {
+        let interval = handler
+            .scripts()
+            .interval
+            .unwrap_or(config.status_pub_interval);
+        // a zero interval would make tokio panic
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval.max(1)));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let mut last_published = None;
+        let mut last_attributes = None;
+        loop {
+            // the line printed by the watch script, when it is the new state itself
+            let watched_state = tokio::select! {
+                _ = ticker.tick() => None,
+                Some(()) = refresh_rx.recv() => None,
+                Some(line) = watch_rx.recv() => {
+                    for refresh_tx in &watch_refresh {
+                        // a full channel already has a refresh pending
+                        let _ = refresh_tx.try_send(());
+                    }
+                    match handler.scripts().watch_mode {
+                        WatchMode::State => Some(line),
+                        WatchMode::Trigger => {
+                            // watchers tend to print bursts of lines for a single change
+                            while watch_rx.try_recv().is_ok() {}
+                            None
+                        }
+                    }
+                }
+            };
+
+            if handler.has_state() {
+                let state_msg = match watched_state {
+                    Some(line) => Ok(handler.state_message(config, line.trim().into())),
+                    None => handler.publish_state(config).await,
+                };
+                Self::publish_handler_state(
+                    config,
+                    client,
+                    &handler.state_topic(config),
+                    state_msg,
+                    &mut last_published,
+                )
+                .await?;
+            }
+
+            if handler.scripts().attributes_script.is_some() {
+                let attributes_msg = handler.publish_attributes(config).await;
+                Self::publish_handler_state(
+                    config,
+                    client,
+                    &handler.attributes_topic(config),
+                    attributes_msg,
+                    &mut last_attributes,
+                )
+                .await?;
+            }
+        }
+    }
+
+    /// Publish the current state of an entity if it changed since `last_published`, or if
+    /// that was longer than `force_update_interval` ago
+    async fn publish_handler_state(
         config: &ComputerAssistantConfig,
         client: &AsyncClient,
+        topic: &str,
+        state_msg: anyhow::Result<mqtt::Message>,
+        last_published: &mut Option<(Vec<u8>, Instant)>,
     ) -> anyhow::Result<()> {
-        for handler in &self.0 {
-            let state_msg = handler.publish_state(config)?;
-            client.publish(state_msg).await?;
+        let state_msg = match state_msg {
+            Ok(state_msg) => state_msg,
+            Err(err) => {
+                // keep the last published value, the script may work on the next run
+                println!("Failed to get the payload for {}: {:#}", topic, err);
+                return Ok(());
+            }
+        };
+
+        let force_update_interval = Duration::from_secs(config.force_update_interval);
+        if is_unchanged(last_published, state_msg.payload(), force_update_interval) {
+            return Ok(());
         }
+
+        let payload = state_msg.payload().to_vec();
+        client.publish(state_msg).await?;
+        *last_published = Some((payload, Instant::now()));
         Ok(())
     }
 }
 
+/// Whether `payload` was already published, less than `force_update_interval` ago
+fn is_unchanged(
+    last_published: &Option<(Vec<u8>, Instant)>,
+    payload: &[u8],
+    force_update_interval: Duration,
+) -> bool {
+    match last_published {
+        Some((last_payload, published_at)) => {
+            last_payload.as_slice() == payload && published_at.elapsed() < force_update_interval
+        }
+        None => false,
+    }
+}
+
 impl Config {
     pub async fn connect_mqtt(
         &self,
@@ -221,98 +746,457 @@ impl Config {
 
     pub fn read_from<R: std::io::Read>(reader: R) -> anyhow::Result<Self> {
         let config: Self = serde_yaml::from_reader(reader)?;
+        config.check_sources()?;
+        config.check_watch_modes()?;
         Ok(config)
     }
 
+    /// Make sure every sensor reading from a source can actually find it
+    fn check_sources(&self) -> anyhow::Result<()> {
+        if let Some(source) = self
+            .source
+            .iter()
+            .find(|source| source.scripts.attributes_script.is_some())
+        {
+            return Err(anyhow::anyhow!(
+                "{} is a source, it has no entity to publish attributes for",
+                source.name.as_str()
+            ));
+        }
+        for sensor in &self.sensor {
+            sensor.source.check(&sensor.name, sensor, &self.source)?;
+        }
+        for binary_sensor in &self.binary_sensor {
+            binary_sensor
+                .source
+                .check(&binary_sensor.name, binary_sensor, &self.source)?;
+        }
+        Ok(())
+    }
+
+    /// Make sure raw watch script lines never replace a state the entity formats itself
+    fn check_watch_modes(&self) -> anyhow::Result<()> {
+        let formatted_states = self
+            .light
+            .iter()
+            .map(|light| (&light.name, light.scripts.watch_mode))
+            .chain(
+                self.cover
+                    .iter()
+                    .map(|cover| (&cover.name, cover.scripts.watch_mode)),
+            )
+            .chain(
+                self.valve
+                    .iter()
+                    .map(|valve| (&valve.name, valve.scripts.watch_mode)),
+            )
+            .chain(
+                self.fan
+                    .iter()
+                    .map(|fan| (&fan.name, fan.scripts.watch_mode)),
+            )
+            .chain(
+                self.update
+                    .iter()
+                    .map(|update| (&update.name, update.scripts.watch_mode)),
+            )
+            .chain(
+                self.media
+                    .iter()
+                    .map(|media| (&media.name, media.scripts.watch_mode)),
+            )
+            .chain(
+                self.presence
+                    .iter()
+                    .map(|presence| (&presence.name, presence.scripts.watch_mode)),
+            )
+            .chain(
+                self.source
+                    .iter()
+                    .map(|source| (&source.name, source.scripts.watch_mode)),
+            )
+            .chain(
+                self.image
+                    .iter()
+                    .map(|image| (&image.name, image.scripts.watch_mode)),
+            );
+
+        for (name, watch_mode) in formatted_states {
+            if watch_mode == WatchMode::State {
+                return Err(anyhow::anyhow!(
+                    "{} formats its own state, so its watch_mode can't be state",
+                    name.as_str()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Register an entity in homeassistant's discovery topic
+    async fn publish_discovery(
+        &self,
+        client: &AsyncClient,
+        component: &str,
+        id: &str,
+        ha_config: &HomeAssistantConfig,
+    ) -> anyhow::Result<()> {
+        let msg = MessageBuilder::new()
+            .topic(format!(
+                "{}/{}/{}/{}/config",
+                self.computer_assistant.homeassistant_topic,
+                component,
+                self.computer_assistant.base_topic,
+                id
+            ))
+            .payload(serde_json::to_vec(ha_config)?)
+            .qos(mqtt::QOS_2)
+            .retained(true)
+            .finalize();
+        client.publish(msg).await?;
+        Ok(())
+    }
+
     pub async fn register_mqtt(
         &mut self,
         client: &AsyncClient,
     ) -> anyhow::Result<(UpdateableHandlers, PublishableHandlers)> {
-        let mut updateable_handlers: UpdateableHandlers = UpdateableHandlers(vec![]);
-        let mut publishable_handlers: PublishableHandlers = PublishableHandlers(vec![]);
+        let mut updateable_handlers = UpdateableHandlers {
+            handlers: vec![],
+            routes: HashMap::new(),
+            refresh: HashMap::new(),
+        };
+        let mut publishable_handlers = PublishableHandlers {
+            handlers: vec![],
+            refresh: vec![],
+            watch_refresh: vec![],
+            events: vec![],
+        };
+        let config = &self.computer_assistant;
 
         for switch in &self.switch {
-            let msg = MessageBuilder::new()
-                .topic(format!(
-                    "{}/switch/{}/{}/config", // register in homeassistant's topic
-                    self.computer_assistant.homeassistant_topic,
-                    self.computer_assistant.base_topic,
-                    switch.name.as_id()
-                ))
-                .payload(serde_json::to_vec(
-                    &switch.ha_config(&self.computer_assistant),
-                )?)
-                .qos(mqtt::QOS_2)
-                .retained(true)
-                .finalize();
-            client.publish(msg).await?;
-
-            let new_switch_updateable = Box::new(switch.clone());
-            let new_switch_publishable = Box::new(switch.clone());
-            updateable_handlers.0.push(new_switch_updateable);
-            publishable_handlers.0.push(new_switch_publishable);
+            self.publish_discovery(
+                client,
+                "switch",
+                &switch.name.as_id(),
+                &switch.ha_config(config),
+            )
+            .await?;
+            if let Some(toggle_button) = switch.toggle_button_ha_config(config) {
+                self.publish_discovery(
+                    client,
+                    "button",
+                    &switch.toggle_button_id(),
+                    &toggle_button,
+                )
+                .await?;
+            }
+
+            updateable_handlers.handlers.push(Box::new(switch.clone()));
+            publishable_handlers.handlers.push(Box::new(switch.clone()));
+        }
+
+        // sources have no entity of their own, sensors read their state from them
+        for source in &self.source {
+            publishable_handlers.handlers.push(Box::new(source.clone()));
         }
 
         for binary_sensor in &self.binary_sensor {
-            let msg = MessageBuilder::new()
-                .topic(format!(
-                    "{}/binary_sensor/{}/{}/config", // register in homeassistant's topic
-                    self.computer_assistant.homeassistant_topic,
-                    self.computer_assistant.base_topic,
-                    binary_sensor.name.as_id()
-                ))
-                .payload(serde_json::to_vec(
-                    &binary_sensor.ha_config(&self.computer_assistant),
-                )?)
-                .qos(mqtt::QOS_2)
-                .retained(true)
-                .finalize();
-            client.publish(msg).await?;
-
-            let new_binary_sensor = Box::new(binary_sensor.clone());
-            publishable_handlers.0.push(new_binary_sensor);
-        }
-
-        for number in &self.number {
-            let msg = MessageBuilder::new()
-                .topic(format!(
-                    "{}/number/{}/{}/config", // register in homeassistant's topic
-                    self.computer_assistant.homeassistant_topic,
-                    self.computer_assistant.base_topic,
-                    number.name.as_id()
-                ))
-                .payload(serde_json::to_vec(
-                    &number.ha_config(&self.computer_assistant),
-                )?)
-                .qos(mqtt::QOS_2)
-                .retained(true)
-                .finalize();
-            client.publish(msg).await?;
-
-            let new_publishable_number = Box::new(number.clone());
-            publishable_handlers.0.push(new_publishable_number);
-            let new_updateable_number = Box::new(number.clone());
-            updateable_handlers.0.push(new_updateable_number);
-        }
-
-        for button in &self.button {
-            let msg = MessageBuilder::new()
-                .topic(format!(
-                    "{}/button/{}/{}/config", // register in homeassistant's topic
-                    self.computer_assistant.homeassistant_topic,
-                    self.computer_assistant.base_topic,
-                    button.name.as_id()
-                ))
-                .payload(serde_json::to_vec(
-                    &button.ha_config(&self.computer_assistant),
-                )?)
-                .qos(mqtt::QOS_2)
-                .retained(true)
-                .finalize();
-            client.publish(msg).await?;
-
-            let new_button = Box::new(button.clone());
-            updateable_handlers.0.push(new_button);
+            self.publish_discovery(
+                client,
+                "binary_sensor",
+                &binary_sensor.name.as_id(),
+                &binary_sensor.ha_config(config),
+            )
+            .await?;
+
+            if !binary_sensor.source.uses_source() {
+                publishable_handlers
+                    .handlers
+                    .push(Box::new(binary_sensor.clone()));
+            }
+        }
+
+        for sensor in &self.sensor {
+            self.publish_discovery(
+                client,
+                "sensor",
+                &sensor.name.as_id(),
+                &sensor.ha_config(config),
+            )
+            .await?;
+
+            if !sensor.source.uses_source() {
+                publishable_handlers.handlers.push(Box::new(sensor.clone()));
+            }
+        }
+
+        // media players are made of regular numbers and buttons
+        let numbers = self
+            .number
+            .iter()
+            .cloned()
+            .chain(self.media.iter().filter_map(Media::volume));
+        for number in numbers {
+            self.publish_discovery(
+                client,
+                "number",
+                &number.name.as_id(),
+                &number.ha_config(config),
+            )
+            .await?;
+
+            publishable_handlers.handlers.push(Box::new(number.clone()));
+            updateable_handlers.handlers.push(Box::new(number));
+        }
+
+        let buttons = self
+            .button
+            .iter()
+            .cloned()
+            .chain(self.media.iter().flat_map(Media::buttons));
+        for button in buttons {
+            self.publish_discovery(
+                client,
+                "button",
+                &button.name.as_id(),
+                &button.ha_config(config),
+            )
+            .await?;
+
+            updateable_handlers.handlers.push(Box::new(button));
+        }
+
+        for scene in &self.scene {
+            self.publish_discovery(
+                client,
+                "scene",
+                &scene.name().as_id(),
+                &scene.ha_config(config),
+            )
+            .await?;
+
+            updateable_handlers.handlers.push(Box::new(scene.clone()));
+        }
+
+        for select in &self.select {
+            let options = match select.options(config).await {
+                Ok(options) => options,
+                Err(err) => {
+                    println!(
+                        "Failed to get options for {}, using the static ones: {:#}",
+                        select.name.as_str(),
+                        err
+                    );
+                    select.options.clone()
+                }
+            };
+            if options.is_empty() {
+                // homeassistant refuses selects without any option
+                println!("No options for {}, skipping it", select.name.as_str());
+                continue;
+            }
+
+            self.publish_discovery(
+                client,
+                "select",
+                &select.name.as_id(),
+                &select.ha_config(config, options),
+            )
+            .await?;
+
+            publishable_handlers.handlers.push(Box::new(select.clone()));
+            updateable_handlers.handlers.push(Box::new(select.clone()));
+        }
+
+        for text in &self.text {
+            self.publish_discovery(client, "text", &text.name.as_id(), &text.ha_config(config))
+                .await?;
+
+            if text.has_state() || text.scripts.attributes_script.is_some() {
+                publishable_handlers.handlers.push(Box::new(text.clone()));
+            }
+            updateable_handlers.handlers.push(Box::new(text.clone()));
+        }
+
+        for light in &self.light {
+            self.publish_discovery(
+                client,
+                "light",
+                &light.name.as_id(),
+                &light.ha_config(config),
+            )
+            .await?;
+
+            publishable_handlers.handlers.push(Box::new(light.clone()));
+            updateable_handlers.handlers.push(Box::new(light.clone()));
+        }
+
+        for event in &self.event {
+            self.publish_discovery(
+                client,
+                "event",
+                &event.name.as_id(),
+                &event.ha_config(config),
+            )
+            .await?;
+            for (id, trigger) in event.device_trigger_ha_configs(config) {
+                self.publish_discovery(client, "device_automation", &id, &trigger)
+                    .await?;
+            }
+
+            publishable_handlers.events.push(event.clone());
+        }
+
+        for notify in &self.notify {
+            self.publish_discovery(
+                client,
+                "notify",
+                &notify.name.as_id(),
+                &notify.ha_config(config),
+            )
+            .await?;
+
+            updateable_handlers.handlers.push(Box::new(notify.clone()));
+        }
+
+        for media in &self.media {
+            for (id, sensor) in media.metadata_ha_configs(config) {
+                self.publish_discovery(client, "sensor", &id, &sensor)
+                    .await?;
+            }
+
+            if media.has_metadata() {
+                publishable_handlers.handlers.push(Box::new(media.clone()));
+            }
+        }
+
+        for lock in &self.lock {
+            self.publish_discovery(client, "lock", &lock.name.as_id(), &lock.ha_config(config))
+                .await?;
+
+            publishable_handlers.handlers.push(Box::new(lock.clone()));
+            updateable_handlers.handlers.push(Box::new(lock.clone()));
+        }
+
+        for cover in &self.cover {
+            self.publish_discovery(
+                client,
+                "cover",
+                &cover.name.as_id(),
+                &cover.ha_config(config),
+            )
+            .await?;
+
+            publishable_handlers.handlers.push(Box::new(cover.clone()));
+            updateable_handlers.handlers.push(Box::new(cover.clone()));
+        }
+
+        for valve in &self.valve {
+            self.publish_discovery(
+                client,
+                "valve",
+                &valve.name.as_id(),
+                &valve.ha_config(config),
+            )
+            .await?;
+
+            publishable_handlers.handlers.push(Box::new(valve.clone()));
+            updateable_handlers.handlers.push(Box::new(valve.clone()));
+        }
+
+        for fan in &self.fan {
+            self.publish_discovery(client, "fan", &fan.name.as_id(), &fan.ha_config(config))
+                .await?;
+
+            publishable_handlers.handlers.push(Box::new(fan.clone()));
+            updateable_handlers.handlers.push(Box::new(fan.clone()));
+        }
+
+        for update in &self.update {
+            self.publish_discovery(
+                client,
+                "update",
+                &update.name.as_id(),
+                &update.ha_config(config),
+            )
+            .await?;
+
+            publishable_handlers.handlers.push(Box::new(update.clone()));
+            if update.can_install() {
+                updateable_handlers.handlers.push(Box::new(update.clone()));
+            }
+        }
+
+        for presence in &self.presence {
+            self.publish_discovery(
+                client,
+                "binary_sensor",
+                &presence.name.as_id(),
+                &presence.ha_config(config),
+            )
+            .await?;
+
+            publishable_handlers
+                .handlers
+                .push(Box::new(presence.clone()));
+        }
+
+        for siren in &self.siren {
+            self.publish_discovery(
+                client,
+                "siren",
+                &siren.name.as_id(),
+                &siren.ha_config(config),
+            )
+            .await?;
+
+            publishable_handlers.handlers.push(Box::new(siren.clone()));
+            updateable_handlers.handlers.push(Box::new(siren.clone()));
+        }
+
+        for image in &self.image {
+            self.publish_discovery(
+                client,
+                "image",
+                &image.name.as_id(),
+                &image.ha_config(config),
+            )
+            .await?;
+            if let Some(capture_button) = image.capture_button_ha_config(config) {
+                self.publish_discovery(
+                    client,
+                    "button",
+                    &image.capture_button_id(),
+                    &capture_button,
+                )
+                .await?;
+                updateable_handlers.handlers.push(Box::new(image.clone()));
+            }
+
+            publishable_handlers.handlers.push(Box::new(image.clone()));
+        }
+
+        for (index, handler) in updateable_handlers.handlers.iter().enumerate() {
+            for topic in handler.command_topics(&self.computer_assistant) {
+                updateable_handlers.routes.insert(topic, index);
+            }
+        }
+
+        for handler in &publishable_handlers.handlers {
+            let (refresh_tx, refresh_rx) = mpsc::channel(1);
+            updateable_handlers
+                .refresh
+                .insert(handler.state_topic(&self.computer_assistant), refresh_tx);
+            publishable_handlers.refresh.push(refresh_rx);
+        }
+
+        for handler in &publishable_handlers.handlers {
+            let watch_refresh = handler
+                .watch_refresh_topics(&self.computer_assistant)
+                .iter()
+                .filter_map(|topic| updateable_handlers.refresh.get(topic).cloned())
+                .collect();
+            publishable_handlers.watch_refresh.push(watch_refresh);
         }
 
         Ok((updateable_handlers, publishable_handlers))
@@ -322,9 +1206,216 @@ impl Config {
 #[cfg(test)]
 mod test {
     use super::*;
+
+    /// Smallest `computer_assistant` section, for tests to add their own entities to
+    const COMPUTER_ASSISTANT: &str = r#"
+computer_assistant:
+  base_topic: computer_assistant
+  name: computer assistant
+  unique_id: computer_assistant
+  status_pub_interval: 2
+  device:
+    ids: [computer]
+    name: computer
+    sw: 1.0.0
+    mf: computer
+    cu: http://localhost
+    model: computer
+"#;
+
     #[test]
     fn test_read_config() {
         let file = std::fs::File::open("config.yaml").expect("Failed to open config file");
         let _config = Config::read_from(file).expect("Failed to read config file");
     }
+
+    #[test]
+    fn test_reject_watch_mode_state_for_formatted_states() {
+        let entities = r#"
+light:
+  - name: keyboard
+    state_script: "true"
+    on_script: "true"
+    off_script: "true"
+    watch_script: echo
+    watch_mode: state
+"#;
+        let config = format!("{}{}", COMPUTER_ASSISTANT, entities);
+        assert!(Config::read_from(config.as_bytes()).is_err());
+        assert!(
+            Config::read_from(config.replace("mode: state", "mode: trigger").as_bytes()).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_dispatch_routes_without_waiting() {
+        let handlers = UpdateableHandlers {
+            handlers: vec![],
+            routes: HashMap::from([
+                ("base/entities/cmd/switch/a".to_string(), 0),
+                ("base/entities/cmd/switch/b".to_string(), 1),
+            ]),
+            refresh: HashMap::new(),
+        };
+        let (a_tx, mut a_rx) = mpsc::channel(1);
+        let (b_tx, mut b_rx) = mpsc::channel(1);
+        let senders = [a_tx, b_tx];
+        let command = |topic: &str, payload: &str| Message::new(topic, payload, mqtt::QOS_1);
+
+        handlers.dispatch(&senders, command("base/entities/cmd/switch/b", "ON"));
+        handlers.dispatch(&senders, command("base/entities/cmd/switch/c", "ON"));
+        assert_eq!(b_rx.try_recv().unwrap().payload_str(), "ON");
+        assert!(a_rx.try_recv().is_err());
+        assert!(b_rx.try_recv().is_err());
+
+        // a busy entity drops its commands instead of holding back the others
+        handlers.dispatch(&senders, command("base/entities/cmd/switch/a", "ON"));
+        handlers.dispatch(&senders, command("base/entities/cmd/switch/a", "OFF"));
+        handlers.dispatch(&senders, command("base/entities/cmd/switch/b", "OFF"));
+        assert_eq!(a_rx.try_recv().unwrap().payload_str(), "ON");
+        assert!(a_rx.try_recv().is_err());
+        assert_eq!(b_rx.try_recv().unwrap().payload_str(), "OFF");
+    }
+
+    #[test]
+    fn test_command_subtopics() {
+        assert_eq!(
+            command_subtopics("base/entities/cmd", "base/entities/cmd/cover/desk/position"),
+            ["cover", "desk", "position"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_debounce_keeps_latest_command() {
+        let (commands_tx, mut commands_rx) = mpsc::channel(8);
+        for brightness in [10, 20, 30] {
+            commands_tx.send(brightness).await.unwrap();
+        }
+        let debounce = Some(Duration::from_millis(50));
+        assert_eq!(next_command(&mut commands_rx, debounce).await, Some(30));
+
+        // without a debounce, every command is applied in order
+        for brightness in [40, 50] {
+            commands_tx.send(brightness).await.unwrap();
+        }
+        assert_eq!(next_command(&mut commands_rx, None).await, Some(40));
+        assert_eq!(next_command(&mut commands_rx, None).await, Some(50));
+
+        // commands arriving during the debounce still replace the first one
+        let sender = tokio::spawn(async move {
+            commands_tx.send(60).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            commands_tx.send(70).await.unwrap();
+        });
+        assert_eq!(next_command(&mut commands_rx, debounce).await, Some(70));
+        sender.await.unwrap();
+        assert_eq!(next_command(&mut commands_rx, debounce).await, None);
+    }
+
+    #[test]
+    fn test_only_publish_changed_states() {
+        let force_update_interval = Duration::from_secs(60);
+        assert!(!is_unchanged(&None, b"ON", force_update_interval));
+
+        let last_published = Some((b"ON".to_vec(), Instant::now()));
+        assert!(is_unchanged(&last_published, b"ON", force_update_interval));
+        assert!(!is_unchanged(
+            &last_published,
+            b"OFF",
+            force_update_interval
+        ));
+
+        // the same state is published again once it is old enough
+        assert!(!is_unchanged(&last_published, b"ON", Duration::ZERO));
+    }
+
+    #[test]
+    fn test_valve_discovery() {
+        let entities = r#"
+valve:
+  - name: radiator
+    state_script: radiator-ctl state
+    open_script: radiator-ctl open
+    close_script: radiator-ctl close
+  - name: tap
+    state_script: tap-ctl state
+    position_script: tap-ctl position
+    open_script: tap-ctl open
+    close_script: tap-ctl close
+    stop_script: tap-ctl stop
+"#;
+        let config = Config::read_from(format!("{}{}", COMPUTER_ASSISTANT, entities).as_bytes())
+            .expect("Failed to read config");
+        let computer_assistant = &config.computer_assistant;
+
+        let radiator = serde_json::to_value(config.valve[0].ha_config(computer_assistant)).unwrap();
+        assert_eq!(radiator["reports_position"], false);
+        assert!(radiator.get("payload_stop").is_none());
+
+        let tap = serde_json::to_value(config.valve[1].ha_config(computer_assistant)).unwrap();
+        assert_eq!(tap["reports_position"], true);
+        assert_eq!(tap["payload_stop"], "STOP");
+    }
+
+    #[test]
+    fn test_entity_metadata_in_discovery() {
+        let entities = r#"
+sensor:
+  - name: cpu temperature
+    device_class: temperature
+    state_class: measurement
+    state_script: cat /sys/class/thermal/thermal_zone0/temp
+media:
+  - name: player
+    icon: mdi:music
+    entity_category: config
+    metadata_script: playerctl-metadata-json
+    metadata_keys: [title]
+    play_pause_script: playerctl play-pause
+    volume_state_script: playerctl volume
+    volume_command_script: playerctl volume "$1"
+"#;
+        let config = Config::read_from(format!("{}{}", COMPUTER_ASSISTANT, entities).as_bytes())
+            .expect("Failed to read config");
+        let computer_assistant = &config.computer_assistant;
+
+        let sensor = serde_json::to_value(config.sensor[0].ha_config(computer_assistant)).unwrap();
+        assert_eq!(sensor["device_class"], "temperature");
+        assert_eq!(sensor["state_class"], "measurement");
+        assert!(sensor.get("icon").is_none());
+        assert!(sensor.get("entity_category").is_none());
+
+        // every entity of a media group gets its metadata
+        let media = &config.media[0];
+        let volume = media.volume().expect("Missing volume");
+        let mut discoveries = vec![volume.ha_config(computer_assistant)];
+        discoveries.extend(
+            media
+                .buttons()
+                .iter()
+                .map(|b| b.ha_config(computer_assistant)),
+        );
+        discoveries.extend(
+            media
+                .metadata_ha_configs(computer_assistant)
+                .into_iter()
+                .map(|(_, sensor)| sensor),
+        );
+        assert_eq!(discoveries.len(), 3);
+        for discovery in discoveries {
+            let discovery = serde_json::to_value(discovery).unwrap();
+            assert_eq!(discovery["icon"], "mdi:music");
+            assert_eq!(discovery["entity_category"], "config");
+        }
+    }
+
+    #[test]
+    fn test_parse_attributes() {
+        assert_eq!(
+            parse_attributes(r#"{"title": "Song", "artist": "Band"}"#).unwrap(),
+            r#"{"artist":"Band","title":"Song"}"#
+        );
+        assert!(parse_attributes("Song - Band").is_err());
+        assert!(parse_attributes(r#"["Song", "Band"]"#).is_err());
+    }
 }
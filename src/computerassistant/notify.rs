@@ -0,0 +1,83 @@
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::HomeAssistantConfig;
+
+use super::{script, ComputerAssistantConfig, EntityMetadata, Name, Updateable};
+
+/// Used when a notify entity has no `command_script`
+const DEFAULT_NOTIFY_SCRIPT: &str =
+    r#"notify-send --app-name=computer-assistant -- "${1:-Home Assistant}" "$2""#;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Notify {
+    pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    /// Title of every notification. Homeassistant only sends the message, so it can't be set
+    /// per notification
+    pub title: Option<String>,
+    /// Receives the title as `$1` and the message as `$2`, also available as the
+    /// `NOTIFY_TITLE` and `NOTIFY_MESSAGE` environment variables. Uses `notify-send` if unset
+    pub command_script: Option<String>,
+    /// Timeout for sending a notification, in seconds
+    pub timeout: Option<u64>,
+}
+
+impl Notify {
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
+        let base_cmd_notify_topic = format!(
+            "{}/entities/cmd/notify/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+
+        HomeAssistantConfig::Notify {
+            base_topic: Default::default(),
+            command_topic: base_cmd_notify_topic,
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.name.to_string(),
+            unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+impl Updateable for Notify {
+    fn update<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            match topic {
+                ["notify", id] if id == &self.name.as_id() => {
+                    let title = self.title.as_deref().unwrap_or_default();
+                    let script = self
+                        .command_script
+                        .as_deref()
+                        .unwrap_or(DEFAULT_NOTIFY_SCRIPT);
+                    script::run_with_env(
+                        script,
+                        &[title, state],
+                        &[("NOTIFY_TITLE", title), ("NOTIFY_MESSAGE", state)],
+                        config.script_timeout(self.timeout),
+                    )
+                    .await?;
+                }
+                _ => {}
+            };
+            Ok(())
+        })
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        vec![format!(
+            "{}/entities/cmd/notify/{}",
+            config.base_topic,
+            self.name.as_id()
+        )]
+    }
+}
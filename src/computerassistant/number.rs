@@ -1,15 +1,19 @@
-use std::process::Command;
-use std::str::from_utf8;
+use std::time::Duration;
 
+use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 
 use crate::HomeAssistantConfig;
 
-use super::{ComputerAssistantConfig, Name, Publishable, Updateable};
+use super::{
+    script, ComputerAssistantConfig, EntityMetadata, Name, Publishable, ScriptOptions, Updateable,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Number {
     pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
     pub state_script: String,
     pub command_script: String,
     #[serde(default)]
@@ -20,6 +24,10 @@ pub struct Number {
     pub step: f64,
     #[serde(default)]
     pub unit_of_measurement: String,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+    /// Only apply the latest value received within this many milliseconds
+    pub debounce_ms: Option<u64>,
 }
 
 impl Number {
@@ -41,6 +49,8 @@ impl Number {
             device: config.device.clone(),
             name: self.name.to_string(),
             unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+            json_attributes_topic: self.json_attributes_topic(config),
             value_template: Some("{{value}}".to_string()),
             command_topic: base_cmd_switch_topic,
             command_template: Some("{{value}}".to_string()),
@@ -65,34 +75,63 @@ impl Publishable for Number {
         )
     }
 
-    fn state_payload(&self, _config: &ComputerAssistantConfig) -> anyhow::Result<String> {
-        // capture stdout and parse it as a float
-        let state = Command::new("bash")
-            .arg("-c")
-            .arg(self.state_script())
-            .output()?;
-
-        let str_state = from_utf8(&state.stdout)?;
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
 
-        Ok(str_state.trim().to_string())
+    fn state_payload<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<String>> {
+        // capture stdout and parse it as a float
+        Box::pin(script::stdout(
+            self.state_script(),
+            &[],
+            config.script_timeout(self.scripts.timeout),
+        ))
     }
 }
 
 impl Updateable for Number {
-    fn update(&self, topic: &[&str], state: &str) -> anyhow::Result<()> {
-        match topic {
-            ["number", id] if id == &self.name.as_id() => {
-                let mut child = Command::new("bash")
-                    .arg("-c")
-                    .arg(&self.command_script)
-                    .arg("computer-assistant")
-                    .arg(state)
-                    .spawn()?;
+    fn update<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            match topic {
+                ["number", id] if id == &self.name.as_id() => {
+                    script::run(
+                        &self.command_script,
+                        &[state],
+                        config.script_timeout(self.scripts.timeout),
+                    )
+                    .await?;
+                }
+                _ => {}
+            };
+            Ok(())
+        })
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        vec![format!(
+            "{}/entities/cmd/number/{}",
+            config.base_topic,
+            self.name.as_id()
+        )]
+    }
+
+    fn refresh_topic(&self, config: &ComputerAssistantConfig) -> Option<String> {
+        Some(Publishable::state_topic(self, config))
+    }
+
+    fn settle_delay(&self) -> Duration {
+        Duration::from_millis(self.scripts.settle_delay_ms)
+    }
 
-                child.wait()?;
-            }
-            _ => {}
-        };
-        Ok(())
+    fn debounce(&self) -> Option<Duration> {
+        self.debounce_ms.map(Duration::from_millis)
     }
 }
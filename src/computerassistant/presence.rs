@@ -0,0 +1,193 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::homeassistant::State;
+use crate::{HomeAssistantConfig, _default_away_after_ms, _default_off_state, _default_on_state};
+
+use super::{script, ComputerAssistantConfig, EntityMetadata, Name, Publishable, ScriptOptions};
+
+/// Whether someone is at the computer, shown as an occupancy binary sensor
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Presence {
+    pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    /// Prints for how long the keyboard and mouse have been idle, in milliseconds
+    pub idle_script: String,
+    /// Exit code tells whether the screen is locked, which always means away
+    pub locked_script: Option<String>,
+    /// Idle time after which nobody is considered present, in milliseconds
+    #[serde(default = "_default_away_after_ms")]
+    pub away_after_ms: u64,
+    /// Once away, how long the idle time must stay below `away_after_ms` before being present
+    /// again, in milliseconds
+    #[serde(default)]
+    pub hysteresis_ms: u64,
+    /// Idle time under which the keyboard or mouse count as being in use, in milliseconds.
+    /// Defaults to the interval between two samples
+    pub active_within_ms: Option<u64>,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+    #[serde(skip)]
+    tracker: Arc<Mutex<PresenceTracker>>,
+}
+
+/// What the hysteresis needs to remember between two idle time samples
+#[derive(Debug, Clone, Default)]
+struct PresenceTracker {
+    present: Option<State>,
+    /// When the keyboard or mouse started being in use while away
+    active_since: Option<Instant>,
+}
+
+impl Presence {
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
+        HomeAssistantConfig::BinarySensor {
+            base_topic: "".to_string(),
+            state_topic: self.state_topic(config),
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.name.to_string(),
+            unique_id: self.name.as_id(),
+            metadata: EntityMetadata {
+                device_class: self
+                    .metadata
+                    .device_class
+                    .clone()
+                    .or_else(|| Some("occupancy".to_string())),
+                ..self.metadata.clone()
+            },
+            value_template: Some("{{value}}".to_string()),
+            state_on: _default_on_state(),
+            state_off: _default_off_state(),
+            json_attributes_topic: self.json_attributes_topic(config),
+        }
+    }
+
+    async fn state(&self, config: &ComputerAssistantConfig) -> anyhow::Result<State> {
+        let timeout = config.script_timeout(self.scripts.timeout);
+        let locked = async {
+            let Some(script) = &self.locked_script else {
+                return Ok(false);
+            };
+            Ok::<_, anyhow::Error>(script::state(script, &[], timeout).await? == State::On)
+        };
+        let (idle, locked) =
+            tokio::try_join!(script::stdout(&self.idle_script, &[], timeout), locked)?;
+        let idle_ms = idle.parse::<f64>()?.round() as u64;
+        let active_within_ms = self
+            .active_within_ms
+            .unwrap_or_else(|| self.scripts.interval.unwrap_or(config.status_pub_interval) * 1000);
+
+        let mut tracker = self.tracker.lock().unwrap();
+        Ok(tracker.update(
+            idle_ms,
+            locked,
+            Instant::now(),
+            self.away_after_ms,
+            active_within_ms,
+            Duration::from_millis(self.hysteresis_ms),
+        ))
+    }
+}
+
+impl PresenceTracker {
+    /// Someone is present while idle for less than `away_after_ms`. Once away, the idle time must
+    /// stay below `active_within_ms` for `hysteresis` to be present again, so a single input
+    /// isn't enough
+    fn update(
+        &mut self,
+        idle_ms: u64,
+        locked: bool,
+        now: Instant,
+        away_after_ms: u64,
+        active_within_ms: u64,
+        hysteresis: Duration,
+    ) -> State {
+        let state = if locked || idle_ms >= away_after_ms {
+            self.active_since = None;
+            State::Off
+        } else if self.present == Some(State::Off) {
+            if idle_ms >= active_within_ms {
+                self.active_since = None;
+                State::Off
+            } else if now.duration_since(*self.active_since.get_or_insert(now)) >= hysteresis {
+                State::On
+            } else {
+                State::Off
+            }
+        } else {
+            State::On
+        };
+        self.present = Some(state);
+        state
+    }
+}
+
+impl Publishable for Presence {
+    fn state_script(&self) -> &str {
+        &self.idle_script
+    }
+
+    fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!(
+            "{}/entities/stat/presence/{}",
+            config.base_topic,
+            self.name.as_id()
+        )
+    }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
+
+    fn state_payload<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<String>> {
+        Box::pin(async move { Ok(self.state(config).await?.to_string()) })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_presence_hysteresis() {
+        let start = Instant::now();
+        let hysteresis = Duration::from_millis(20_000);
+        let mut tracker = PresenceTracker::default();
+        // samples every 10s, away after a minute
+        let mut sample = |at_ms: u64, idle_ms: u64, locked: bool| {
+            let now = start + Duration::from_millis(at_ms);
+            tracker.update(idle_ms, locked, now, 60_000, 10_000, hysteresis)
+        };
+
+        assert_eq!(sample(0, 1_000, false), State::On);
+        assert_eq!(sample(10_000, 1_000, true), State::Off);
+        assert_eq!(sample(20_000, 0, false), State::Off);
+
+        // going idle keeps it away
+        assert_eq!(sample(90_000, 60_000, false), State::Off);
+        assert_eq!(sample(100_000, 2_000, false), State::Off);
+        assert_eq!(sample(110_000, 12_000, false), State::Off);
+        assert_eq!(sample(120_000, 22_000, false), State::Off);
+        assert_eq!(sample(130_000, 32_000, false), State::Off);
+
+        // one input followed by no other isn't enough to be back
+        assert_eq!(sample(180_000, 3_000, false), State::Off);
+        assert_eq!(sample(190_000, 13_000, false), State::Off);
+        assert_eq!(sample(200_000, 23_000, false), State::Off);
+        assert_eq!(sample(210_000, 33_000, false), State::Off);
+
+        // being in use for the whole hysteresis is
+        assert_eq!(sample(220_000, 500, false), State::Off);
+        assert_eq!(sample(230_000, 1_000, false), State::Off);
+        assert_eq!(sample(240_000, 200, false), State::On);
+        assert_eq!(sample(250_000, 40_000, false), State::On);
+    }
+}
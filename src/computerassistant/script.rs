@@ -0,0 +1,210 @@
+use std::future::Future;
+use std::os::unix::process::CommandExt;
+use std::process::{ExitStatus, Output, Stdio};
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+use crate::homeassistant::State;
+
+const WATCH_RESTART_DELAY: Duration = Duration::from_secs(5);
+
+/// Build the bash invocation for a script.
+///
+/// Extra arguments are available to the script as `$1`, `$2`, ...
+///
+/// Scripts run in their own process group, so that everything they spawned can be killed
+/// together if they time out.
+fn command(script: &str, args: &[&str]) -> Command {
+    let mut command = std::process::Command::new("bash");
+    command
+        .arg("-c")
+        .arg(script)
+        .arg("computer-assistant")
+        .args(args)
+        .process_group(0);
+
+    let mut command = Command::from(command);
+    command.kill_on_drop(true);
+    command
+}
+
+fn kill_process_group(pid: u32) {
+    // SAFETY: kill has no memory safety requirements, a negative pid targets the process group
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+/// Kills the whole process group of a script once dropped. `kill_on_drop` only gets bash itself,
+/// which would leave the rest of a pipeline running
+struct ProcessGroupGuard(Option<u32>);
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        if let Some(pid) = self.0 {
+            kill_process_group(pid);
+        }
+    }
+}
+
+/// Wait for a script, killing its whole process group if it takes longer than `timeout`
+async fn wait_for<T>(
+    pid: Option<u32>,
+    timeout: Option<Duration>,
+    wait: impl Future<Output = std::io::Result<T>>,
+) -> anyhow::Result<T> {
+    let Some(timeout) = timeout else {
+        return Ok(wait.await?);
+    };
+
+    match tokio::time::timeout(timeout, wait).await {
+        Ok(result) => Ok(result?),
+        Err(_) => {
+            if let Some(pid) = pid {
+                kill_process_group(pid);
+            }
+            Err(anyhow::anyhow!(
+                "script timed out after {}s and was killed",
+                timeout.as_secs_f64()
+            ))
+        }
+    }
+}
+
+/// Run a script to completion, letting its output go to our own stdout/stderr
+pub async fn run(
+    script: &str,
+    args: &[&str],
+    timeout: Option<Duration>,
+) -> anyhow::Result<ExitStatus> {
+    run_with_env(script, args, &[], timeout).await
+}
+
+/// Like [`run`], with extra environment variables for the script
+pub async fn run_with_env(
+    script: &str,
+    args: &[&str],
+    envs: &[(&str, &str)],
+    timeout: Option<Duration>,
+) -> anyhow::Result<ExitStatus> {
+    let mut child = command(script, args).envs(envs.iter().copied()).spawn()?;
+    let pid = child.id();
+
+    wait_for(pid, timeout, child.wait()).await
+}
+
+/// Run a script to completion, capturing its stdout
+pub async fn output(
+    script: &str,
+    args: &[&str],
+    timeout: Option<Duration>,
+) -> anyhow::Result<Output> {
+    let child = command(script, args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+    let pid = child.id();
+
+    wait_for(pid, timeout, child.wait_with_output()).await
+}
+
+/// Run a long-running script, sending every line it prints to `lines`.
+///
+/// The script is restarted whenever it exits. Only returns once `lines` is closed.
+pub async fn watch(script: &str, lines: mpsc::Sender<String>) -> anyhow::Result<()> {
+    loop {
+        match watch_once(script, &lines).await {
+            Ok(()) => println!("Watch script exited: {}", script.trim()),
+            Err(err) => println!("Watch script failed: {}: {:#}", script.trim(), err),
+        }
+        if lines.is_closed() {
+            return Ok(());
+        }
+        tokio::time::sleep(WATCH_RESTART_DELAY).await;
+    }
+}
+
+async fn watch_once(script: &str, lines: &mpsc::Sender<String>) -> anyhow::Result<()> {
+    let mut child = command(script, &[])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+    let _group = ProcessGroupGuard(child.id());
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow::anyhow!("watch script has no stdout"))?;
+
+    let mut stdout_lines = BufReader::new(stdout).lines();
+    while let Some(line) = stdout_lines.next_line().await? {
+        if lines.send(line).await.is_err() {
+            // nobody is listening anymore, the script is killed on drop
+            return Ok(());
+        }
+    }
+    child.wait().await?;
+    Ok(())
+}
+
+/// Run a script and capture its stdout as a trimmed string
+pub async fn stdout(
+    script: &str,
+    args: &[&str],
+    timeout: Option<Duration>,
+) -> anyhow::Result<String> {
+    let output = output(script, args, timeout).await?;
+
+    Ok(std::str::from_utf8(&output.stdout)?.trim().to_string())
+}
+
+/// Run a script and interpret its exit code as an ON/OFF state
+pub async fn state(
+    script: &str,
+    args: &[&str],
+    timeout: Option<Duration>,
+) -> anyhow::Result<State> {
+    let status = run(script, args, timeout).await?;
+
+    Ok(if status.success() {
+        State::On
+    } else {
+        State::Off
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_script_timeout() {
+        let started = std::time::Instant::now();
+        let result = run("sleep 10", &[], Some(Duration::from_millis(100))).await;
+
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_watch_kills_pipeline() {
+        let (lines_tx, mut lines_rx) = mpsc::channel(1);
+        // the subshell prints its own pid, then becomes the sleep at the start of the pipeline
+        let watcher = tokio::spawn(async move {
+            watch_once("(echo $BASHPID; exec sleep 30) | cat", &lines_tx).await
+        });
+        let pid: libc::pid_t = lines_rx.recv().await.unwrap().parse().unwrap();
+
+        watcher.abort();
+        let _ = watcher.await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // killed processes may linger as zombies, but must not be running anymore
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+        assert!(stat.is_empty() || stat.contains(") Z "), "{}", stat);
+    }
+}
@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::HomeAssistantConfig;
+
+use super::{
+    script, ComputerAssistantConfig, EntityMetadata, Name, Publishable, ScriptOptions, Updateable,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Select {
+    pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    pub state_script: String,
+    /// Receives the chosen option as `$1`
+    pub command_script: String,
+    #[serde(default)]
+    pub options: Vec<String>,
+    /// Script listing the available options, one per line. Takes precedence over `options`.
+    /// It only runs once at startup, so options added later show up after a restart
+    pub options_script: Option<String>,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+}
+
+impl Select {
+    /// Get the available options, running `options_script` if there is one
+    pub async fn options(&self, config: &ComputerAssistantConfig) -> anyhow::Result<Vec<String>> {
+        let Some(options_script) = &self.options_script else {
+            return Ok(self.options.clone());
+        };
+
+        let options = script::stdout(
+            options_script,
+            &[],
+            config.script_timeout(self.scripts.timeout),
+        )
+        .await?
+        .lines()
+        .map(str::trim)
+        .filter(|option| !option.is_empty())
+        .map(str::to_string)
+        .collect();
+        Ok(options)
+    }
+
+    pub fn ha_config(
+        &self,
+        config: &ComputerAssistantConfig,
+        options: Vec<String>,
+    ) -> HomeAssistantConfig {
+        let base_cmd_select_topic = format!(
+            "{}/entities/cmd/select/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        let base_stat_select_topic = format!(
+            "{}/entities/stat/select/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        HomeAssistantConfig::Select {
+            base_topic: "".to_string(),
+            state_topic: base_stat_select_topic,
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.name.to_string(),
+            unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+            json_attributes_topic: self.json_attributes_topic(config),
+            value_template: Some("{{value}}".to_string()),
+            command_topic: base_cmd_select_topic,
+            options,
+        }
+    }
+}
+
+impl Publishable for Select {
+    fn state_script(&self) -> &str {
+        &self.state_script
+    }
+
+    fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!(
+            "{}/entities/stat/select/{}",
+            config.base_topic,
+            self.name.as_id()
+        )
+    }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
+
+    fn state_payload<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<String>> {
+        // the selected option is whatever the script writes to stdout
+        Box::pin(script::stdout(
+            self.state_script(),
+            &[],
+            config.script_timeout(self.scripts.timeout),
+        ))
+    }
+}
+
+impl Updateable for Select {
+    fn update<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            match topic {
+                ["select", id] if id == &self.name.as_id() => {
+                    script::run(
+                        &self.command_script,
+                        &[state],
+                        config.script_timeout(self.scripts.timeout),
+                    )
+                    .await?;
+                }
+                _ => {}
+            };
+            Ok(())
+        })
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        vec![format!(
+            "{}/entities/cmd/select/{}",
+            config.base_topic,
+            self.name.as_id()
+        )]
+    }
+
+    fn refresh_topic(&self, config: &ComputerAssistantConfig) -> Option<String> {
+        Some(Publishable::state_topic(self, config))
+    }
+
+    fn settle_delay(&self) -> Duration {
+        Duration::from_millis(self.scripts.settle_delay_ms)
+    }
+}
@@ -1,12 +1,95 @@
+use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 
-use super::{DisplayPrecision, Name};
+use crate::HomeAssistantConfig;
+
+use super::source::SourceValue;
+use super::{
+    script, ComputerAssistantConfig, DisplayPrecision, EntityMetadata, Name, Publishable,
+    ScriptOptions,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Sensor {
     pub name: Name,
-    pub state_script: String,
-    pub unit_of_measurement: String,
-    #[serde(default)]
-    pub suggested_display_precision: DisplayPrecision,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    /// Required unless the state is read from a source
+    pub state_script: Option<String>,
+    #[serde(flatten)]
+    pub source: SourceValue,
+    pub unit_of_measurement: Option<String>,
+    pub suggested_display_precision: Option<DisplayPrecision>,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+}
+
+impl Sensor {
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
+        let base_stat_sensor_topic = format!(
+            "{}/entities/stat/sensor/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+
+        // text sensors (no unit) must not carry a display precision, otherwise homeassistant
+        // will refuse their non-numeric states
+        let suggested_display_precision =
+            match (&self.unit_of_measurement, &self.suggested_display_precision) {
+                (_, Some(precision)) => Some(precision.clone().into()),
+                (Some(_), None) => Some(DisplayPrecision::default().into()),
+                (None, None) => None,
+            };
+
+        HomeAssistantConfig::Sensor {
+            base_topic: "".to_string(),
+            state_topic: self
+                .source
+                .state_topic(config)
+                .unwrap_or(base_stat_sensor_topic),
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.name.to_string(),
+            unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+            value_template: Some(
+                self.source
+                    .value_template()
+                    .unwrap_or_else(|| "{{value}}".to_string()),
+            ),
+            unit_of_measurement: self.unit_of_measurement.clone(),
+            suggested_display_precision,
+            json_attributes_topic: self.json_attributes_topic(config),
+        }
+    }
+}
+
+impl Publishable for Sensor {
+    fn state_script(&self) -> &str {
+        self.state_script.as_deref().unwrap_or_default()
+    }
+
+    fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!(
+            "{}/entities/stat/sensor/{}",
+            config.base_topic,
+            self.name.as_id()
+        )
+    }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
+
+    fn state_payload<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<String>> {
+        // the state of a sensor is whatever the script writes to stdout
+        Box::pin(script::stdout(
+            self.state_script(),
+            &[],
+            config.script_timeout(self.scripts.timeout),
+        ))
+    }
 }
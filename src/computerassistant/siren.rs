@@ -0,0 +1,178 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::homeassistant::State;
+use crate::HomeAssistantConfig;
+
+use super::{
+    switch, ComputerAssistantConfig, EntityMetadata, Name, Publishable, ScriptOptions, Updateable,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Siren {
+    pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    /// Exit code tells whether the siren is on
+    pub state_script: String,
+    /// Receives the tone as `$1`, the duration in seconds as `$2` and the volume, between 0 and
+    /// 1, as `$3`. They are empty when homeassistant doesn't send them
+    pub on_script: String,
+    pub off_script: String,
+    #[serde(default)]
+    pub available_tones: Vec<String>,
+    /// Whether the `on_script` handles a duration
+    #[serde(default)]
+    pub support_duration: bool,
+    /// Whether the `on_script` handles a volume
+    #[serde(default)]
+    pub support_volume_set: bool,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+}
+
+/// Command payload homeassistant sends to sirens
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SirenCommand {
+    pub state: State,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tone: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume_level: Option<f64>,
+}
+
+impl SirenCommand {
+    /// Parse the JSON homeassistant sends, or a plain `ON`/`OFF`
+    pub fn parse(payload: &str) -> anyhow::Result<Self> {
+        if let Ok(command) = serde_json::from_str(payload) {
+            return Ok(command);
+        }
+        Ok(SirenCommand {
+            state: State::from_str(payload)?,
+            tone: None,
+            duration: None,
+            volume_level: None,
+        })
+    }
+}
+
+impl Siren {
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
+        let base_cmd_siren_topic = format!(
+            "{}/entities/cmd/siren/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        HomeAssistantConfig::Siren {
+            base_topic: "".to_string(),
+            command_topic: base_cmd_siren_topic,
+            state_topic: self.state_topic(config),
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.name.to_string(),
+            unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+            available_tones: (!self.available_tones.is_empty())
+                .then(|| self.available_tones.clone()),
+            support_duration: self.support_duration,
+            support_volume_set: self.support_volume_set,
+            json_attributes_topic: self.json_attributes_topic(config),
+        }
+    }
+}
+
+impl Updateable for Siren {
+    fn update<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            match topic {
+                ["siren", id] if id == &self.name.as_id() => {
+                    let command = SirenCommand::parse(state)?;
+                    let tone = command.tone.unwrap_or_default();
+                    let duration = command
+                        .duration
+                        .map(|duration| duration.to_string())
+                        .unwrap_or_default();
+                    let volume = command
+                        .volume_level
+                        .map(|volume| volume.to_string())
+                        .unwrap_or_default();
+                    switch::set_state(
+                        &self.on_script,
+                        &self.off_script,
+                        command.state,
+                        &[&tone, &duration, &volume],
+                        config.script_timeout(self.scripts.timeout),
+                    )
+                    .await?;
+                }
+                _ => {}
+            };
+            Ok(())
+        })
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        vec![format!(
+            "{}/entities/cmd/siren/{}",
+            config.base_topic,
+            self.name.as_id()
+        )]
+    }
+
+    fn refresh_topic(&self, config: &ComputerAssistantConfig) -> Option<String> {
+        Some(Publishable::state_topic(self, config))
+    }
+
+    fn settle_delay(&self) -> Duration {
+        Duration::from_millis(self.scripts.settle_delay_ms)
+    }
+}
+
+impl Publishable for Siren {
+    fn state_script(&self) -> &str {
+        &self.state_script
+    }
+
+    fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!(
+            "{}/entities/stat/siren/{}",
+            config.base_topic,
+            self.name.as_id()
+        )
+    }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_siren_command() {
+        assert_eq!(
+            SirenCommand::parse(r#"{"state": "ON", "tone": "doorbell", "volume_level": 0.5}"#)
+                .unwrap(),
+            SirenCommand {
+                state: State::On,
+                tone: Some("doorbell".to_string()),
+                duration: None,
+                volume_level: Some(0.5),
+            }
+        );
+        assert_eq!(SirenCommand::parse("OFF").unwrap().state, State::Off);
+        assert!(SirenCommand::parse("LOUD").is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{script, ComputerAssistantConfig, Name, Publishable, ScriptOptions};
+
+/// A script printing a JSON document that many sensors take their state from, so it only runs
+/// once for all of them
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Source {
+    pub name: Name,
+    /// Prints a JSON document
+    pub script: String,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+}
+
+/// Where a sensor reads its state from, when it uses a source instead of its own script
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SourceValue {
+    /// Name of the source
+    pub source: Option<Name>,
+    /// Template extracting the value from the source's JSON, e.g. `{{ value_json.cpu.temp }}`
+    pub value_template: Option<String>,
+    /// JSON pointer to the value in the source's JSON, e.g. `/cpu/temp`. Numeric segments
+    /// index into arrays
+    pub json_pointer: Option<String>,
+}
+
+impl SourceValue {
+    pub fn uses_source(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// State topic of the source, if there is one
+    pub fn state_topic(&self, config: &ComputerAssistantConfig) -> Option<String> {
+        self.source
+            .as_ref()
+            .map(|source| source_topic(config, source))
+    }
+
+    /// Template extracting the value, from either `value_template` or `json_pointer`
+    pub fn value_template(&self) -> Option<String> {
+        self.value_template.clone().or_else(|| {
+            self.json_pointer
+                .as_deref()
+                .map(|pointer| format!("{{{{ {} }}}}", pointer_expression(pointer)))
+        })
+    }
+
+    /// Like [`Self::value_template`], but mapping truthy values to `ON` and the rest to `OFF`
+    /// when using a JSON pointer
+    pub fn binary_value_template(&self) -> Option<String> {
+        self.value_template.clone().or_else(|| {
+            self.json_pointer.as_deref().map(|pointer| {
+                format!(
+                    "{{{{ 'ON' if {} else 'OFF' }}}}",
+                    pointer_expression(pointer)
+                )
+            })
+        })
+    }
+
+    /// Make sure the entity either has its own state script or reads a known source. Reading a
+    /// source leaves nothing for the entity's own scripts, interval or timeout to do
+    pub fn check(
+        &self,
+        name: &Name,
+        entity: &dyn Publishable,
+        sources: &[Source],
+    ) -> anyhow::Result<()> {
+        let Some(source) = &self.source else {
+            if entity.state_script().is_empty() {
+                return Err(anyhow::anyhow!(
+                    "{} needs either a state_script or a source",
+                    name.as_str()
+                ));
+            }
+            return Ok(());
+        };
+
+        if !sources.iter().any(|s| s.name.as_id() == source.as_id()) {
+            return Err(anyhow::anyhow!(
+                "{} reads from unknown source {}",
+                name.as_str(),
+                source.as_str()
+            ));
+        }
+        if self.value_template.is_none() && self.json_pointer.is_none() {
+            return Err(anyhow::anyhow!(
+                "{} needs a value_template or a json_pointer to read from {}",
+                name.as_str(),
+                source.as_str()
+            ));
+        }
+
+        let unused = [
+            ("state_script", !entity.state_script().is_empty()),
+            (
+                "attributes_script",
+                entity.scripts().attributes_script.is_some(),
+            ),
+            ("watch_script", entity.scripts().watch_script.is_some()),
+            ("interval", entity.scripts().interval.is_some()),
+            ("timeout", entity.scripts().timeout.is_some()),
+        ];
+        if let Some((option, _)) = unused.iter().find(|(_, set)| *set) {
+            return Err(anyhow::anyhow!(
+                "{} reads from source {}, so it can't have a {} of its own",
+                name.as_str(),
+                source.as_str(),
+                option
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn source_topic(config: &ComputerAssistantConfig, source: &Name) -> String {
+    format!(
+        "{}/entities/stat/source/{}",
+        config.base_topic,
+        source.as_id()
+    )
+}
+
+/// Turn a JSON pointer such as `/cpu/0/temp` into the template expression
+/// `value_json["cpu"][0]["temp"]`
+fn pointer_expression(pointer: &str) -> String {
+    let mut expression = "value_json".to_string();
+    for segment in pointer.split('/').skip(1) {
+        let segment = segment.replace("~1", "/").replace("~0", "~");
+        if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) {
+            expression.push_str(&format!("[{}]", segment));
+        } else {
+            expression.push_str(&format!("[{}]", Value::from(segment)));
+        }
+    }
+    expression
+}
+
+impl Publishable for Source {
+    fn state_script(&self) -> &str {
+        &self.script
+    }
+
+    fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
+        source_topic(config, &self.name)
+    }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
+
+    fn state_payload<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<String>> {
+        Box::pin(async move {
+            let output = script::stdout(
+                &self.script,
+                &[],
+                config.script_timeout(self.scripts.timeout),
+            )
+            .await?;
+            // catch broken output here rather than in every template using it
+            let document: Value = serde_json::from_str(&output)?;
+            Ok(document.to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::sensor::Sensor;
+    use super::*;
+
+    #[test]
+    fn test_pointer_expression() {
+        assert_eq!(
+            pointer_expression("/cpu/0/temp"),
+            r#"value_json["cpu"][0]["temp"]"#
+        );
+        assert_eq!(
+            pointer_expression("/k10temp-pci-00c3/Tctl/temp1_input"),
+            r#"value_json["k10temp-pci-00c3"]["Tctl"]["temp1_input"]"#
+        );
+        assert_eq!(
+            pointer_expression("/a~1b/c~0d"),
+            r#"value_json["a/b"]["c~d"]"#
+        );
+        assert_eq!(pointer_expression(""), "value_json");
+    }
+
+    #[test]
+    fn test_check_source_value() {
+        let sources: Vec<Source> =
+            serde_yaml::from_str("[{name: sensors, script: sensors -j}]").unwrap();
+        let check = |yaml: &str| {
+            let sensor: Sensor = serde_yaml::from_str(yaml).unwrap();
+            sensor.source.check(&sensor.name, &sensor, &sources)
+        };
+
+        assert!(check("{name: cpu, source: sensors, json_pointer: /cpu}").is_ok());
+        assert!(check("{name: cpu, state_script: cpu-temp}").is_ok());
+        assert!(check("{name: cpu}").is_err());
+        assert!(check("{name: cpu, source: gpu, json_pointer: /cpu}").is_err());
+        assert!(check("{name: cpu, source: sensors}").is_err());
+        assert!(
+            check("{name: cpu, source: sensors, json_pointer: /cpu, state_script: cpu-temp}")
+                .is_err()
+        );
+        assert!(check(
+            "{name: cpu, source: sensors, json_pointer: /cpu, attributes_script: cpu-info}"
+        )
+        .is_err());
+    }
+}
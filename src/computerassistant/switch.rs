@@ -1,20 +1,31 @@
-use std::process::Command;
-use std::str::{from_utf8, FromStr};
+use std::str::FromStr;
+use std::time::Duration;
 
+use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 
-use crate::homeassistant::State;
+use crate::homeassistant::{State, SwitchCommand};
 use crate::{HomeAssistantConfig, _default_off_state, _default_on_state};
 
-use super::{ComputerAssistantConfig, Name, Publishable, Updateable};
+use super::{
+    script, ComputerAssistantConfig, EntityMetadata, Name, Publishable, ScriptOptions, Updateable,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Switch {
     pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
     pub state_script: String,
     pub on_script: String,
     pub off_script: String,
+    /// Script run on a `TOGGLE` command. Without it, the on/off scripts are used instead
     pub toggle_script: Option<String>,
+    /// Also register a button that toggles this switch
+    #[serde(default)]
+    pub toggle_button: bool,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
 }
 
 impl Switch {
@@ -38,30 +49,118 @@ impl Switch {
             device: config.device.clone(),
             name: self.name.to_string(),
             unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+            json_attributes_topic: self.json_attributes_topic(config),
             value_template: Some("{{value}}".to_string()),
             state_on: _default_on_state(),
             state_off: _default_off_state(),
-            // entity_category: "diagnostic".to_string(),
         }
     }
-}
 
-impl Updateable for Switch {
-    fn update(&self, topic: &[&str], state: &str) -> anyhow::Result<()> {
-        match topic {
-            ["switch", id] if id == &self.name.as_id() => {
-                let script = match State::from_str(state)? {
-                    State::On => &self.on_script,
-                    State::Off => &self.off_script,
-                };
-                Command::new("bash").arg("-c").arg(script).spawn()?.wait()?;
-            }
-            _ => {}
+    /// Configuration of the companion button toggling this switch, if enabled
+    pub fn toggle_button_ha_config(
+        &self,
+        config: &ComputerAssistantConfig,
+    ) -> Option<HomeAssistantConfig> {
+        if !self.toggle_button {
+            return None;
+        }
+
+        let base_cmd_switch_topic = format!(
+            "{}/entities/cmd/switch/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+
+        Some(HomeAssistantConfig::Button {
+            base_topic: Default::default(),
+            command_topic: base_cmd_switch_topic,
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: format!("{} toggle", self.name.as_str()),
+            unique_id: self.toggle_button_id(),
+            metadata: Default::default(),
+            value_template: Some("{{value}}".to_string()),
+            payload_press: "TOGGLE".to_string(),
+        })
+    }
+
+    pub fn toggle_button_id(&self) -> String {
+        format!("{}_toggle", self.name.as_id())
+    }
+
+    async fn toggle(&self, config: &ComputerAssistantConfig) -> anyhow::Result<()> {
+        let timeout = config.script_timeout(self.scripts.timeout);
+        if let Some(toggle_script) = &self.toggle_script {
+            script::run(toggle_script, &[], timeout).await?;
+            return Ok(());
+        }
+
+        let script = match script::state(&self.state_script, &[], timeout).await? {
+            State::On => &self.off_script,
+            State::Off => &self.on_script,
         };
+        script::run(script, &[], timeout).await?;
         Ok(())
     }
 }
 
+/// Run the on or off script of anything switched like a switch, passing `args` along to it
+pub async fn set_state(
+    on_script: &str,
+    off_script: &str,
+    state: State,
+    args: &[&str],
+    timeout: Option<Duration>,
+) -> anyhow::Result<()> {
+    let script = match state {
+        State::On => on_script,
+        State::Off => off_script,
+    };
+    script::run(script, args, timeout).await?;
+    Ok(())
+}
+
+impl Updateable for Switch {
+    fn update<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            match topic {
+                ["switch", id] if id == &self.name.as_id() => {
+                    let state = match SwitchCommand::from_str(state)? {
+                        SwitchCommand::Set(state) => state,
+                        SwitchCommand::Toggle => return self.toggle(config).await,
+                    };
+                    let timeout = config.script_timeout(self.scripts.timeout);
+                    set_state(&self.on_script, &self.off_script, state, &[], timeout).await?;
+                }
+                _ => {}
+            };
+            Ok(())
+        })
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        vec![format!(
+            "{}/entities/cmd/switch/{}",
+            config.base_topic,
+            self.name.as_id()
+        )]
+    }
+
+    fn refresh_topic(&self, config: &ComputerAssistantConfig) -> Option<String> {
+        Some(Publishable::state_topic(self, config))
+    }
+
+    fn settle_delay(&self) -> Duration {
+        Duration::from_millis(self.scripts.settle_delay_ms)
+    }
+}
+
 impl Publishable for Switch {
     fn state_script(&self) -> &str {
         &self.state_script
@@ -74,4 +173,8 @@ impl Publishable for Switch {
             self.name.as_id()
         )
     }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
 }
@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::{HomeAssistantConfig, _default_max_text, _default_min_text};
+
+use super::{
+    script, ComputerAssistantConfig, EntityMetadata, Name, Publishable, ScriptOptions, Updateable,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TextMode {
+    #[default]
+    Text,
+    Password,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Text {
+    pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    /// Receives the text as `$1`
+    pub command_script: String,
+    /// Without a state script, homeassistant just assumes the last text sent is the state
+    pub state_script: Option<String>,
+    /// Minimum length of the text
+    #[serde(default = "_default_min_text")]
+    pub min: usize,
+    /// Maximum length of the text
+    #[serde(default = "_default_max_text")]
+    pub max: usize,
+    /// Regex the text must match
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub mode: TextMode,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+}
+
+impl Text {
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
+        let base_cmd_text_topic = format!(
+            "{}/entities/cmd/text/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        let base_stat_text_topic = self.state_script.as_ref().map(|_| {
+            format!(
+                "{}/entities/stat/text/{}",
+                config.base_topic,
+                self.name.as_id()
+            )
+        });
+        HomeAssistantConfig::Text {
+            base_topic: "".to_string(),
+            state_topic: base_stat_text_topic,
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.name.to_string(),
+            unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+            json_attributes_topic: self.json_attributes_topic(config),
+            value_template: Some("{{value}}".to_string()),
+            command_topic: base_cmd_text_topic,
+            min: self.min,
+            max: self.max,
+            pattern: self.pattern.clone(),
+            mode: self.mode,
+        }
+    }
+}
+
+impl Publishable for Text {
+    fn state_script(&self) -> &str {
+        self.state_script.as_deref().unwrap_or_default()
+    }
+
+    fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!(
+            "{}/entities/stat/text/{}",
+            config.base_topic,
+            self.name.as_id()
+        )
+    }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
+
+    fn has_state(&self) -> bool {
+        self.state_script.is_some()
+    }
+
+    fn state_payload<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<String>> {
+        // the text is whatever the script writes to stdout
+        Box::pin(script::stdout(
+            self.state_script(),
+            &[],
+            config.script_timeout(self.scripts.timeout),
+        ))
+    }
+}
+
+impl Updateable for Text {
+    fn update<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            match topic {
+                ["text", id] if id == &self.name.as_id() => {
+                    script::run(
+                        &self.command_script,
+                        &[state],
+                        config.script_timeout(self.scripts.timeout),
+                    )
+                    .await?;
+                }
+                _ => {}
+            };
+            Ok(())
+        })
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        vec![format!(
+            "{}/entities/cmd/text/{}",
+            config.base_topic,
+            self.name.as_id()
+        )]
+    }
+
+    fn refresh_topic(&self, config: &ComputerAssistantConfig) -> Option<String> {
+        self.has_state()
+            .then(|| Publishable::state_topic(self, config))
+    }
+
+    fn settle_delay(&self) -> Duration {
+        Duration::from_millis(self.scripts.settle_delay_ms)
+    }
+}
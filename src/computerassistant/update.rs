@@ -0,0 +1,170 @@
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::HomeAssistantConfig;
+
+use super::{
+    script, ComputerAssistantConfig, EntityMetadata, Name, Publishable, ScriptOptions, Updateable,
+};
+
+/// Homeassistant refuses release summaries longer than this
+const MAX_RELEASE_SUMMARY_CHARS: usize = 255;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Update {
+    pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    /// Title of the software, shown in homeassistant
+    pub title: Option<String>,
+    /// Prints the installed version
+    pub installed_version_script: String,
+    /// Prints the latest available version. An update is pending whenever it differs from the
+    /// installed one
+    pub latest_version_script: String,
+    /// Prints a short description of what the update brings
+    pub release_summary_script: Option<String>,
+    /// Installs the update. Without it, the update can only be seen from homeassistant
+    pub install_script: Option<String>,
+    /// Timeout for the install script, in seconds. Installing can take a while, so there is
+    /// none by default
+    pub install_timeout: Option<u64>,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+}
+
+/// State payload homeassistant's update platform expects
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateState {
+    pub installed_version: String,
+    pub latest_version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_summary: Option<String>,
+}
+
+impl Update {
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
+        let base_cmd_update_topic = format!(
+            "{}/entities/cmd/update/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        let base_stat_update_topic = format!(
+            "{}/entities/stat/update/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        HomeAssistantConfig::Update {
+            base_topic: "".to_string(),
+            command_topic: self.install_script.as_ref().map(|_| base_cmd_update_topic),
+            state_topic: base_stat_update_topic,
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.name.to_string(),
+            unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+            json_attributes_topic: self.json_attributes_topic(config),
+            payload_install: self.install_script.as_ref().map(|_| "install".to_string()),
+        }
+    }
+
+    pub fn can_install(&self) -> bool {
+        self.install_script.is_some()
+    }
+
+    async fn state(&self, config: &ComputerAssistantConfig) -> anyhow::Result<UpdateState> {
+        let timeout = config.script_timeout(self.scripts.timeout);
+        let release_summary = async {
+            let Some(script) = &self.release_summary_script else {
+                return Ok(None);
+            };
+            let summary = script::stdout(script, &[], timeout).await?;
+            Ok::<_, anyhow::Error>(Some(
+                summary.chars().take(MAX_RELEASE_SUMMARY_CHARS).collect(),
+            ))
+        };
+        let (installed_version, latest_version, release_summary) = tokio::try_join!(
+            script::stdout(&self.installed_version_script, &[], timeout),
+            script::stdout(&self.latest_version_script, &[], timeout),
+            release_summary
+        )?;
+
+        Ok(UpdateState {
+            installed_version,
+            latest_version,
+            title: self.title.clone(),
+            release_summary,
+        })
+    }
+}
+
+impl Publishable for Update {
+    fn state_script(&self) -> &str {
+        &self.installed_version_script
+    }
+
+    fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!(
+            "{}/entities/stat/update/{}",
+            config.base_topic,
+            self.name.as_id()
+        )
+    }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
+
+    fn state_payload<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<String>> {
+        Box::pin(async move { Ok(serde_json::to_string(&self.state(config).await?)?) })
+    }
+}
+
+impl Updateable for Update {
+    fn update<'a>(
+        &'a self,
+        _config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            match topic {
+                ["update", id] if id == &self.name.as_id() => {
+                    let Some(install_script) = &self.install_script else {
+                        return Ok(());
+                    };
+                    if state != "install" {
+                        return Ok(());
+                    }
+                    let timeout = self.install_timeout.map(Duration::from_secs);
+                    script::run(install_script, &[], timeout).await?;
+                }
+                _ => {}
+            };
+            Ok(())
+        })
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        vec![format!(
+            "{}/entities/cmd/update/{}",
+            config.base_topic,
+            self.name.as_id()
+        )]
+    }
+
+    fn refresh_topic(&self, config: &ComputerAssistantConfig) -> Option<String> {
+        Some(Publishable::state_topic(self, config))
+    }
+
+    fn settle_delay(&self) -> Duration {
+        Duration::from_millis(self.scripts.settle_delay_ms)
+    }
+}
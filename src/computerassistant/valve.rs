@@ -0,0 +1,163 @@
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::HomeAssistantConfig;
+
+use super::cover::CoverState;
+use super::{
+    script, ComputerAssistantConfig, EntityMetadata, Name, Publishable, ScriptOptions, Updateable,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Valve {
+    pub name: Name,
+    #[serde(flatten)]
+    pub metadata: EntityMetadata,
+    /// Prints one of `open`, `opening`, `closed` or `closing`
+    pub state_script: String,
+    pub open_script: String,
+    pub close_script: String,
+    pub stop_script: Option<String>,
+    /// Prints the current position, between 0 (closed) and 100 (open). The valve only reports
+    /// a position when there is one
+    pub position_script: Option<String>,
+    /// Receives the position as `$1`
+    pub set_position_script: Option<String>,
+    #[serde(flatten)]
+    pub scripts: ScriptOptions,
+}
+
+impl Valve {
+    fn reports_position(&self) -> bool {
+        self.position_script.is_some()
+    }
+
+    pub fn ha_config(&self, config: &ComputerAssistantConfig) -> HomeAssistantConfig {
+        let base_cmd_valve_topic = format!(
+            "{}/entities/cmd/valve/{}",
+            config.base_topic,
+            self.name.as_id()
+        );
+        // homeassistant reads the state and position from the JSON state on its own
+        HomeAssistantConfig::Valve {
+            base_topic: "".to_string(),
+            command_topic: base_cmd_valve_topic,
+            state_topic: self.state_topic(config),
+            availability_topic: format!("{}/{}", config.base_topic, config.availability_topic),
+            device: config.device.clone(),
+            name: self.name.to_string(),
+            unique_id: self.name.as_id(),
+            metadata: self.metadata.clone(),
+            json_attributes_topic: self.json_attributes_topic(config),
+            payload_stop: self.stop_script.as_ref().map(|_| "STOP".to_string()),
+            reports_position: self.reports_position(),
+        }
+    }
+
+    async fn state(&self, config: &ComputerAssistantConfig) -> anyhow::Result<CoverState> {
+        let timeout = config.script_timeout(self.scripts.timeout);
+        let position = async {
+            let Some(position_script) = &self.position_script else {
+                return Ok(None);
+            };
+            let output = script::stdout(position_script, &[], timeout).await?;
+            Ok::<_, anyhow::Error>(Some(output.parse::<f64>()?.round() as u64))
+        };
+        let (state, position) =
+            tokio::try_join!(script::stdout(&self.state_script, &[], timeout), position)?;
+
+        Ok(CoverState { state, position })
+    }
+
+    /// Run the script for a command, which is a position when the valve reports one
+    async fn command(&self, config: &ComputerAssistantConfig, command: &str) -> anyhow::Result<()> {
+        let timeout = config.script_timeout(self.scripts.timeout);
+        let script = match command {
+            "OPEN" => &self.open_script,
+            "CLOSE" => &self.close_script,
+            "STOP" => self
+                .stop_script
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("No stop_script configured"))?,
+            position => {
+                let position = position
+                    .parse::<f64>()
+                    .map_err(|_| anyhow::anyhow!("Unknown valve command: {}", command))?;
+                match &self.set_position_script {
+                    Some(script) => {
+                        script::run(script, &[&position.round().to_string()], timeout).await?;
+                        return Ok(());
+                    }
+                    // without a position script, only fully open or closed can be reached
+                    None if position >= 100.0 => &self.open_script,
+                    None if position <= 0.0 => &self.close_script,
+                    None => return Err(anyhow::anyhow!("No set_position_script configured")),
+                }
+            }
+        };
+        script::run(script, &[], timeout).await?;
+        Ok(())
+    }
+}
+
+impl Publishable for Valve {
+    fn state_script(&self) -> &str {
+        &self.state_script
+    }
+
+    fn state_topic(&self, config: &ComputerAssistantConfig) -> String {
+        format!(
+            "{}/entities/stat/valve/{}",
+            config.base_topic,
+            self.name.as_id()
+        )
+    }
+
+    fn scripts(&self) -> &ScriptOptions {
+        &self.scripts
+    }
+
+    fn state_payload<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+    ) -> BoxFuture<'a, anyhow::Result<String>> {
+        Box::pin(async move { Ok(serde_json::to_string(&self.state(config).await?)?) })
+    }
+}
+
+impl Updateable for Valve {
+    fn update<'a>(
+        &'a self,
+        config: &'a ComputerAssistantConfig,
+        topic: &'a [&'a str],
+        state: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            match topic {
+                ["valve", id] if id == &self.name.as_id() => {
+                    self.command(config, state).await?;
+                }
+                _ => {}
+            };
+            Ok(())
+        })
+    }
+
+    fn command_topics(&self, config: &ComputerAssistantConfig) -> Vec<String> {
+        vec![format!(
+            "{}/entities/cmd/valve/{}",
+            config.base_topic,
+            self.name.as_id()
+        )]
+    }
+
+    fn refresh_topic(&self, config: &ComputerAssistantConfig) -> Option<String> {
+        Some(Publishable::state_topic(self, config))
+    }
+
+    fn settle_delay(&self) -> Duration {
+        Duration::from_millis(self.scripts.settle_delay_ms)
+    }
+}
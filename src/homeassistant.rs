@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::fmt::Display;
 use std::str::FromStr;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
@@ -8,11 +9,11 @@ pub enum State {
     Off,
 }
 
-impl ToString for State {
-    fn to_string(&self) -> String {
+impl Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            State::On => "ON".to_string(),
-            State::Off => "OFF".to_string(),
+            State::On => write!(f, "ON"),
+            State::Off => write!(f, "OFF"),
         }
     }
 }
@@ -29,55 +30,20 @@ impl FromStr for State {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub struct HomeAssistantDevice {
-    ids: Vec<String>,
-    #[serde(alias = "sw")]
-    version: String,
-    #[serde(alias = "mf")]
-    manufacturer: String,
-    #[serde(alias = "cu")]
-    ip: String,
-    #[serde(alias = "mdl")]
-    model: String,
-    name: String,
+/// Payload received on the command topic of a switch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwitchCommand {
+    Set(State),
+    Toggle,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(untagged, rename_all = "snake_case")]
-pub enum HomeAssistantConfig {
-    Switch {
-        #[serde(rename = "~")]
-        base_topic: String,
-        #[serde(rename = "cmd_t")]
-        command_topic: String,
-        #[serde(rename = "stat_t")]
-        state_topic: String,
-        #[serde(rename = "dev")]
-        device: HomeAssistantDevice,
-        name: String,
-        #[serde(rename = "uniq_id")]
-        unique_id: String,
-        value_template: String,
-        entity_category: String,
-    },
-}
-
-pub trait Switch {
-    /// Get the current state of the switch
-    fn get_state(&self) -> State;
-
-    /// Set the state of the switch based on an arbitrary payload sent by homeassistant
-    ///
-    /// The payload is usually "ON" or "OFF" but can be configured differently in homeassistant
-    fn handle_set_state(&self, payload: &str);
-
-    /// Check if this switch should handle_set_state for a specific topic.
-    ///
-    /// topic is a list of subtopics, split by "/". The base topic is already stripped out.
-    fn should_handle_topic(&self, topic: &[&str]) -> bool;
+impl FromStr for SwitchCommand {
+    type Err = anyhow::Error;
 
-    /// Get the homeassistant configuration for this switch
-    fn get_homeassistant_config(&self) -> HomeAssistantConfig;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "TOGGLE" => Ok(SwitchCommand::Toggle),
+            _ => Ok(SwitchCommand::Set(State::from_str(s)?)),
+        }
+    }
 }
@@ -82,18 +82,13 @@ async fn main() -> Result<(), anyhow::Error> {
         let (updateable_handler, publishable_handler) = cfg.register_mqtt(&cli).await?;
         println!("Registered with Home Assistant");
 
-        let update_interval =
-            std::time::Duration::from_secs(cfg.computer_assistant.status_pub_interval);
         let new_cli = cli.clone();
         let publishing_computer_assistant_cfg = cfg.computer_assistant.clone();
         let update_states_handle: JoinHandle<Result<(), anyhow::Error>> =
             tokio::spawn(async move {
-                loop {
-                    publishable_handler
-                        .publish_state_mqtt(&publishing_computer_assistant_cfg, &new_cli)
-                        .await?;
-                    tokio::time::sleep(update_interval).await;
-                }
+                publishable_handler
+                    .publish_state_mqtt(&publishing_computer_assistant_cfg, &new_cli)
+                    .await
             });
 
         updateable_handler
@@ -143,10 +138,38 @@ fn _default_step_number() -> f64 {
     1.0
 }
 
+fn _default_min_text() -> usize {
+    0
+}
+
+fn _default_max_text() -> usize {
+    255
+}
+
+fn _default_brightness_scale() -> u64 {
+    100
+}
+
+fn _default_light_schema() -> String {
+    "json".to_string()
+}
+
 fn _default_payload_press() -> String {
     "PRESS".to_string()
 }
 
+fn _default_away_after_ms() -> u64 {
+    5 * 60 * 1000
+}
+
+fn _default_image_content_type() -> String {
+    "image/png".to_string()
+}
+
+fn _default_media_metadata_keys() -> Vec<String> {
+    vec!["title".to_string(), "artist".to_string()]
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged, rename_all = "snake_case")]
 enum HomeAssistantConfig {
@@ -169,6 +192,14 @@ enum HomeAssistantConfig {
         state_off: String,
         #[serde(rename = "avty_t")]
         availability_topic: String,
+        #[serde(
+            rename = "json_attr_t",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        json_attributes_topic: Option<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
     },
     BinarySensor {
         #[serde(rename = "~", default)]
@@ -187,6 +218,40 @@ enum HomeAssistantConfig {
         state_off: String,
         #[serde(rename = "avty_t")]
         availability_topic: String,
+        #[serde(
+            rename = "json_attr_t",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        json_attributes_topic: Option<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    Sensor {
+        #[serde(rename = "~", default)]
+        base_topic: String,
+        #[serde(rename = "stat_t")]
+        state_topic: String,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
+        name: String,
+        #[serde(rename = "uniq_id")]
+        unique_id: String,
+        value_template: Option<String>,
+        #[serde(rename = "avty_t")]
+        availability_topic: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        unit_of_measurement: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        suggested_display_precision: Option<u8>,
+        #[serde(
+            rename = "json_attr_t",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        json_attributes_topic: Option<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
     },
     Number {
         #[serde(rename = "~", default)]
@@ -212,6 +277,139 @@ enum HomeAssistantConfig {
         step: f64,
         #[serde(default)]
         unit_of_measurement: String,
+        #[serde(
+            rename = "json_attr_t",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        json_attributes_topic: Option<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    Select {
+        #[serde(rename = "~", default)]
+        base_topic: String,
+        #[serde(rename = "cmd_t")]
+        command_topic: String,
+        #[serde(rename = "stat_t")]
+        state_topic: String,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
+        name: String,
+        #[serde(rename = "uniq_id")]
+        unique_id: String,
+        value_template: Option<String>,
+        #[serde(rename = "avty_t")]
+        availability_topic: String,
+        #[serde(rename = "ops")]
+        options: Vec<String>,
+        #[serde(
+            rename = "json_attr_t",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        json_attributes_topic: Option<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    Text {
+        #[serde(rename = "~", default)]
+        base_topic: String,
+        #[serde(rename = "cmd_t")]
+        command_topic: String,
+        #[serde(rename = "stat_t", default, skip_serializing_if = "Option::is_none")]
+        state_topic: Option<String>,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
+        name: String,
+        #[serde(rename = "uniq_id")]
+        unique_id: String,
+        value_template: Option<String>,
+        #[serde(rename = "avty_t")]
+        availability_topic: String,
+        #[serde(default = "_default_min_text")]
+        min: usize,
+        #[serde(default = "_default_max_text")]
+        max: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pattern: Option<String>,
+        #[serde(default)]
+        mode: computerassistant::TextMode,
+        #[serde(
+            rename = "json_attr_t",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        json_attributes_topic: Option<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    Light {
+        #[serde(rename = "~", default)]
+        base_topic: String,
+        #[serde(default = "_default_light_schema")]
+        schema: String,
+        #[serde(rename = "cmd_t")]
+        command_topic: String,
+        #[serde(rename = "stat_t")]
+        state_topic: String,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
+        name: String,
+        #[serde(rename = "uniq_id")]
+        unique_id: String,
+        #[serde(rename = "avty_t")]
+        availability_topic: String,
+        #[serde(default)]
+        brightness: bool,
+        #[serde(default = "_default_brightness_scale")]
+        brightness_scale: u64,
+        supported_color_modes: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min_mireds: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_mireds: Option<u64>,
+        #[serde(
+            rename = "json_attr_t",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        json_attributes_topic: Option<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    Event {
+        #[serde(rename = "~", default)]
+        base_topic: String,
+        #[serde(rename = "stat_t")]
+        state_topic: String,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
+        name: String,
+        #[serde(rename = "uniq_id")]
+        unique_id: String,
+        #[serde(rename = "avty_t")]
+        availability_topic: String,
+        #[serde(rename = "evt_typ")]
+        event_types: Vec<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    DeviceTrigger {
+        #[serde(rename = "atype")]
+        automation_type: String,
+        #[serde(rename = "t")]
+        topic: String,
+        #[serde(rename = "type")]
+        trigger_type: String,
+        #[serde(rename = "stype")]
+        subtype: String,
+        #[serde(rename = "pl")]
+        payload: String,
+        #[serde(rename = "val_tpl")]
+        value_template: String,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
     },
     Button {
         #[serde(rename = "~", default)]
@@ -228,5 +426,235 @@ enum HomeAssistantConfig {
         payload_press: String,
         #[serde(rename = "avty_t")]
         availability_topic: String,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    Lock {
+        #[serde(rename = "~", default)]
+        base_topic: String,
+        #[serde(rename = "cmd_t")]
+        command_topic: String,
+        #[serde(rename = "stat_t")]
+        state_topic: String,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
+        name: String,
+        #[serde(rename = "uniq_id")]
+        unique_id: String,
+        value_template: Option<String>,
+        #[serde(rename = "avty_t")]
+        availability_topic: String,
+        #[serde(
+            rename = "json_attr_t",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        json_attributes_topic: Option<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    Cover {
+        #[serde(rename = "~", default)]
+        base_topic: String,
+        #[serde(rename = "cmd_t")]
+        command_topic: String,
+        #[serde(rename = "stat_t")]
+        state_topic: String,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
+        name: String,
+        #[serde(rename = "uniq_id")]
+        unique_id: String,
+        value_template: Option<String>,
+        #[serde(rename = "avty_t")]
+        availability_topic: String,
+        #[serde(default)]
+        payload_stop: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position_topic: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position_template: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        set_position_topic: Option<String>,
+        #[serde(
+            rename = "json_attr_t",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        json_attributes_topic: Option<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    Valve {
+        #[serde(rename = "~", default)]
+        base_topic: String,
+        #[serde(rename = "cmd_t")]
+        command_topic: String,
+        #[serde(rename = "stat_t")]
+        state_topic: String,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
+        name: String,
+        #[serde(rename = "uniq_id")]
+        unique_id: String,
+        #[serde(rename = "avty_t")]
+        availability_topic: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        payload_stop: Option<String>,
+        #[serde(default)]
+        reports_position: bool,
+        #[serde(
+            rename = "json_attr_t",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        json_attributes_topic: Option<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    Fan {
+        #[serde(rename = "~", default)]
+        base_topic: String,
+        #[serde(rename = "cmd_t")]
+        command_topic: String,
+        #[serde(rename = "stat_t")]
+        state_topic: String,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
+        name: String,
+        #[serde(rename = "uniq_id")]
+        unique_id: String,
+        state_value_template: String,
+        #[serde(rename = "avty_t")]
+        availability_topic: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        percentage_command_topic: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        percentage_state_topic: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        percentage_value_template: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        preset_mode_command_topic: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        preset_mode_state_topic: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        preset_mode_value_template: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        preset_modes: Option<Vec<String>>,
+        #[serde(
+            rename = "json_attr_t",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        json_attributes_topic: Option<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    Scene {
+        #[serde(rename = "~", default)]
+        base_topic: String,
+        #[serde(rename = "cmd_t")]
+        command_topic: String,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
+        name: String,
+        #[serde(rename = "uniq_id")]
+        unique_id: String,
+        #[serde(rename = "pl_on")]
+        payload_on: String,
+        #[serde(rename = "avty_t")]
+        availability_topic: String,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    Update {
+        #[serde(rename = "~", default)]
+        base_topic: String,
+        #[serde(rename = "cmd_t", default, skip_serializing_if = "Option::is_none")]
+        command_topic: Option<String>,
+        #[serde(rename = "stat_t")]
+        state_topic: String,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
+        name: String,
+        #[serde(rename = "uniq_id")]
+        unique_id: String,
+        #[serde(rename = "avty_t")]
+        availability_topic: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        payload_install: Option<String>,
+        #[serde(
+            rename = "json_attr_t",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        json_attributes_topic: Option<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    Siren {
+        #[serde(rename = "~", default)]
+        base_topic: String,
+        #[serde(rename = "cmd_t")]
+        command_topic: String,
+        #[serde(rename = "stat_t")]
+        state_topic: String,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
+        name: String,
+        #[serde(rename = "uniq_id")]
+        unique_id: String,
+        #[serde(rename = "avty_t")]
+        availability_topic: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        available_tones: Option<Vec<String>>,
+        #[serde(default)]
+        support_duration: bool,
+        #[serde(default)]
+        support_volume_set: bool,
+        #[serde(
+            rename = "json_attr_t",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        json_attributes_topic: Option<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    Image {
+        #[serde(rename = "~", default)]
+        base_topic: String,
+        image_topic: String,
+        content_type: String,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
+        name: String,
+        #[serde(rename = "uniq_id")]
+        unique_id: String,
+        #[serde(rename = "avty_t")]
+        availability_topic: String,
+        #[serde(
+            rename = "json_attr_t",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        json_attributes_topic: Option<String>,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
+    },
+    Notify {
+        #[serde(rename = "~", default)]
+        base_topic: String,
+        #[serde(rename = "cmd_t")]
+        command_topic: String,
+        #[serde(rename = "dev")]
+        device: HomeAssistantDevice,
+        name: String,
+        #[serde(rename = "uniq_id")]
+        unique_id: String,
+        #[serde(rename = "avty_t")]
+        availability_topic: String,
+        #[serde(flatten)]
+        metadata: computerassistant::EntityMetadata,
     },
 }